@@ -0,0 +1,259 @@
+use rusqlite::{Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+pub const BACKUP_FORMAT_ID: &str = "tauri-chat-app.backup";
+pub const BACKUP_VERSION: u32 = 1;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const DATABASE_ENTRY: &str = "chat.db";
+const IMAGES_PREFIX: &str = "images";
+
+/// Describes an archive; written first so a restore can check it up front.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format: String,
+    pub version: u32,
+    pub created_at: String,
+    pub schema_version: i32,
+    /// Where the images lived when the backup was taken; `image_path`
+    /// values start with it and are rebased on restore.
+    pub images_dir: String,
+    /// The database and images are encrypted; restoring needs the passphrase
+    /// that was set when the backup was taken.
+    #[serde(default)]
+    pub encrypted: bool,
+}
+
+/// An archive unpacked into a staging directory and checked, ready to be
+/// swapped in for the live data.
+pub struct StagedRestore {
+    pub manifest: BackupManifest,
+    pub database: PathBuf,
+    pub images_dir: PathBuf,
+    pub image_count: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RestoreReport {
+    pub backup_created_at: String,
+    pub conversations: usize,
+    pub images: usize,
+    /// Where the data that was replaced has been kept.
+    pub previous_database: String,
+    pub previous_images: Option<String>,
+    /// Whether the restored data is encrypted.
+    pub encrypted: bool,
+}
+
+/// Zips a database snapshot and the images directory into `destination`,
+/// returning the number of images written.
+pub fn write_archive(
+    snapshot: &Path,
+    manifest: &BackupManifest,
+    images_dir: &Path,
+    destination: &Path,
+) -> Result<usize, String> {
+    let file = File::create(destination).map_err(|e| format!("Failed to create backup file: {}", e))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .large_file(true);
+
+    let manifest_json = serde_json::to_vec_pretty(manifest).map_err(|e| format!("Failed to write manifest: {}", e))?;
+    zip.start_file(MANIFEST_ENTRY, options).map_err(archive_error)?;
+    io::Write::write_all(&mut zip, &manifest_json).map_err(|e| format!("Failed to write manifest: {}", e))?;
+
+    zip.start_file(DATABASE_ENTRY, options).map_err(archive_error)?;
+    let mut database = File::open(snapshot).map_err(|e| format!("Failed to read database snapshot: {}", e))?;
+    io::copy(&mut database, &mut zip).map_err(|e| format!("Failed to write database to backup: {}", e))?;
+
+    let mut image_count = 0;
+    let mut pending = vec![images_dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let Ok(relative) = path.strip_prefix(images_dir) else {
+                continue;
+            };
+            // Archive names always use '/', whatever the platform
+            let mut name = String::from(IMAGES_PREFIX);
+            for component in relative.components() {
+                name.push('/');
+                name.push_str(&component.as_os_str().to_string_lossy());
+            }
+
+            zip.start_file(name, options).map_err(archive_error)?;
+            let mut image = File::open(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            io::copy(&mut image, &mut zip).map_err(|e| format!("Failed to write image to backup: {}", e))?;
+            image_count += 1;
+        }
+    }
+
+    zip.finish().map_err(archive_error)?;
+    Ok(image_count)
+}
+
+/// Unpacks `archive` into `staging_dir` and checks it: the manifest must be
+/// ours, every entry must stay inside the staging directory, and the
+/// database must pass an integrity check at a schema this build can open.
+/// `passphrase` is only used for encrypted backups.
+pub fn stage_restore(
+    archive: &Path,
+    staging_dir: &Path,
+    latest_schema: i32,
+    passphrase: Option<&str>,
+) -> Result<StagedRestore, String> {
+    let file = File::open(archive).map_err(|e| format!("Failed to open backup: {}", e))?;
+    let mut zip = ZipArchive::new(file).map_err(|e| format!("Not a valid backup archive: {}", e))?;
+
+    let manifest: BackupManifest = {
+        let entry = zip.by_name(MANIFEST_ENTRY).map_err(|_| "Backup is missing its manifest.".to_string())?;
+        serde_json::from_reader(entry).map_err(|e| format!("Invalid backup manifest: {}", e))?
+    };
+    if manifest.format != BACKUP_FORMAT_ID {
+        return Err("This file is not a backup made by this app.".to_string());
+    }
+    if manifest.version > BACKUP_VERSION {
+        return Err("This backup was made by a newer version of the app. Please update the app.".to_string());
+    }
+    if manifest.encrypted && passphrase.is_none() {
+        return Err("This backup is encrypted. Enter its passphrase to restore it.".to_string());
+    }
+
+    fs::create_dir_all(staging_dir).map_err(|e| format!("Failed to create restore directory: {}", e))?;
+    let database = staging_dir.join(DATABASE_ENTRY);
+    let images_dir = staging_dir.join(IMAGES_PREFIX);
+    fs::create_dir_all(&images_dir).map_err(|e| format!("Failed to create restore directory: {}", e))?;
+
+    let mut has_database = false;
+    let mut image_count = 0;
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index).map_err(archive_error)?;
+        let name = entry.enclosed_name()
+            .ok_or_else(|| format!("Backup contains an unsafe path: {}", entry.name()))?;
+        if entry.is_dir() {
+            continue;
+        }
+
+        let target = if name == Path::new(DATABASE_ENTRY) {
+            has_database = true;
+            database.clone()
+        } else if name.starts_with(IMAGES_PREFIX) {
+            image_count += 1;
+            staging_dir.join(&name)
+        } else {
+            continue;
+        };
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create restore directory: {}", e))?;
+        }
+        let mut output = File::create(&target).map_err(|e| format!("Failed to extract backup: {}", e))?;
+        io::copy(&mut entry, &mut output).map_err(|e| format!("Failed to extract backup: {}", e))?;
+    }
+    if !has_database {
+        return Err("Backup does not contain a database.".to_string());
+    }
+
+    check_database(&database, latest_schema, staged_passphrase(&manifest, passphrase))?;
+    Ok(StagedRestore { manifest, database, images_dir, image_count })
+}
+
+/// The key for the staged database: the passphrase if the backup is
+/// encrypted, nothing otherwise.
+pub fn staged_passphrase<'a>(manifest: &BackupManifest, passphrase: Option<&'a str>) -> Option<&'a str> {
+    passphrase.filter(|_| manifest.encrypted)
+}
+
+fn open_staged(database: &Path, flags: OpenFlags, passphrase: Option<&str>) -> Result<Connection, String> {
+    let conn = Connection::open_with_flags(database, flags)
+        .map_err(|e| format!("Backup database cannot be opened: {}", e))?;
+    if let Some(passphrase) = passphrase {
+        conn.pragma_update(None, "key", passphrase)
+            .map_err(|e| format!("Backup database cannot be opened: {}", e))?;
+    }
+    match conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |_| Ok(())) {
+        Ok(()) => Ok(conn),
+        Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == rusqlite::ErrorCode::NotADatabase => {
+            Err(match passphrase {
+                Some(_) => "Wrong passphrase for this backup.".to_string(),
+                None => "Backup database is encrypted or not a database.".to_string(),
+            })
+        }
+        Err(e) => Err(format!("Backup database cannot be read: {}", e)),
+    }
+}
+
+fn check_database(database: &Path, latest_schema: i32, passphrase: Option<&str>) -> Result<(), String> {
+    let conn = open_staged(database, OpenFlags::SQLITE_OPEN_READ_ONLY, passphrase)?;
+    let integrity: String = conn.query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(|e| format!("Backup database cannot be read: {}", e))?;
+    if integrity != "ok" {
+        return Err(format!("Backup database is corrupt: {}", integrity));
+    }
+    let schema_version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(|e| format!("Backup database cannot be read: {}", e))?;
+    if schema_version > latest_schema {
+        return Err("This backup was made by a newer version of the app. Please update the app.".to_string());
+    }
+    Ok(())
+}
+
+/// Rebases every image path under `old_images_dir` onto `new_images_dir`,
+/// so a backup taken on another machine points at the restored files.
+/// Returns how many paths were rewritten.
+pub fn rewrite_image_paths(
+    database: &Path,
+    old_images_dir: &str,
+    new_images_dir: &Path,
+    passphrase: Option<&str>,
+) -> Result<usize, String> {
+    let conn = open_staged(database, OpenFlags::default(), passphrase)?;
+    // Backups from before attachments are migrated after this, so their paths are still on `messages`
+    let has_attachments: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'attachments'",
+        [],
+        |row| row.get(0),
+    ).map_err(|e| format!("Database error: {}", e))?;
+    let (table, column) = if has_attachments { ("attachments", "path") } else { ("messages", "image_path") };
+
+    let paths: Vec<(i32, String)> = {
+        let mut stmt = conn.prepare(&format!("SELECT id, {0} FROM {1} WHERE {0} IS NOT NULL", column, table))
+            .map_err(|e| format!("Database error: {}", e))?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| format!("Database error: {}", e))?;
+        rows.collect::<rusqlite::Result<_>>().map_err(|e| format!("Database error: {}", e))?
+    };
+
+    let mut rewritten = 0;
+    for (id, image_path) in paths {
+        let Some(relative) = image_path.strip_prefix(old_images_dir) else {
+            continue;
+        };
+        // The backup may come from a platform with the other separator
+        let mut new_path = new_images_dir.to_path_buf();
+        new_path.extend(relative.split(['/', '\\']).filter(|part| !part.is_empty()));
+
+        conn.execute(
+            &format!("UPDATE {} SET {} = ?1 WHERE id = ?2", table, column),
+            rusqlite::params![new_path.to_string_lossy(), id],
+        ).map_err(|e| format!("Database error: {}", e))?;
+        rewritten += 1;
+    }
+    Ok(rewritten)
+}
+
+fn archive_error(e: zip::result::ZipError) -> String {
+    format!("Backup archive error: {}", e)
+}
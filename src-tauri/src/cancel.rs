@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::oneshot;
+
+/// Tracks long-running requests so a separate command can abort them. Each
+/// running task holds a `Registration`; firing the sender cancels it, and
+/// dropping the registration removes the task once it is done.
+pub struct CancelRegistry<K> {
+    // Each sender is stored with the token of the registration that owns it
+    senders: Mutex<HashMap<K, (u64, oneshot::Sender<()>)>>,
+    next_token: AtomicU64,
+}
+
+/// A running task's entry in a `CancelRegistry`, removed when dropped so
+/// every return path of the task clears it.
+pub struct Registration<'a, K: Eq + Hash> {
+    registry: &'a CancelRegistry<K>,
+    key: K,
+    token: u64,
+    receiver: oneshot::Receiver<()>,
+}
+
+impl<K: Eq + Hash> Registration<'_, K> {
+    /// Resolves once the task is cancelled. A sender dropped without firing
+    /// is not a cancellation, so this then never resolves.
+    pub async fn cancelled(&mut self) {
+        if (&mut self.receiver).await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+impl<K: Eq + Hash> Drop for Registration<'_, K> {
+    fn drop(&mut self) {
+        self.registry.finish(&self.key, self.token);
+    }
+}
+
+impl<K: Eq + Hash + Clone> CancelRegistry<K> {
+    pub fn new() -> Self {
+        Self {
+            senders: Mutex::new(HashMap::new()),
+            next_token: AtomicU64::new(0),
+        }
+    }
+
+    /// Registers a task under `key`. Returns `None` if one is already running.
+    pub fn register(&self, key: K) -> Result<Option<Registration<'_, K>>, String> {
+        let mut senders = self.senders.lock().map_err(|e| format!("Cancel registry lock error: {}", e))?;
+        if senders.contains_key(&key) {
+            return Ok(None);
+        }
+        let token = self.next_token.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = oneshot::channel();
+        senders.insert(key.clone(), (token, sender));
+        Ok(Some(Registration { registry: self, key, token, receiver }))
+    }
+
+    /// Signals the task under `key`. Returns `false` if nothing was running.
+    pub fn cancel(&self, key: &K) -> Result<bool, String> {
+        let mut senders = self.senders.lock().map_err(|e| format!("Cancel registry lock error: {}", e))?;
+        match senders.remove(key) {
+            Some((_, sender)) => Ok(sender.send(()).is_ok()),
+            None => Ok(false),
+        }
+    }
+}
+
+impl<K: Eq + Hash> CancelRegistry<K> {
+    // Forgets a finished task. A task registered under the same key since it
+    // was cancelled has another token and is left alone.
+    fn finish(&self, key: &K, token: u64) {
+        if let Ok(mut senders) = self.senders.lock() {
+            if senders.get(key).is_some_and(|(registered, _)| *registered == token) {
+                senders.remove(key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn finishing_a_cancelled_task_keeps_its_successor() {
+        let registry = CancelRegistry::new();
+        let mut old = registry.register(1).unwrap().unwrap();
+        assert!(registry.cancel(&1).unwrap());
+        old.cancelled().await;
+
+        let mut new = registry.register(1).unwrap().unwrap();
+        drop(old);
+
+        assert!(registry.register(1).unwrap().is_none());
+        assert!(registry.cancel(&1).unwrap());
+        new.cancelled().await;
+    }
+
+    #[test]
+    fn dropping_a_registration_frees_the_key() {
+        let registry = CancelRegistry::new();
+        let registration = registry.register(1).unwrap().unwrap();
+        assert!(registry.register(1).unwrap().is_none());
+        drop(registration);
+        assert!(registry.register(1).unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn dropped_sender_is_not_a_cancellation() {
+        let registry = CancelRegistry::new();
+        let mut registration = registry.register(1).unwrap().unwrap();
+        registry.senders.lock().unwrap().clear();
+
+        let cancelled = tokio::time::timeout(std::time::Duration::from_millis(20), registration.cancelled()).await;
+        assert!(cancelled.is_err());
+    }
+}
@@ -0,0 +1,408 @@
+use crate::db::Message;
+use crate::ollama::{ChatMessage, GenerationOptions};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Ollama's context length when `num_ctx` is not set. OpenAI-compatible
+/// servers do not report theirs, so set `num_ctx` to match the server.
+pub const DEFAULT_CONTEXT_TOKENS: usize = 4096;
+
+// Rough characters per token for the common tokenizers on English text
+const CHARS_PER_TOKEN: usize = 4;
+// Role markers and separators the chat template adds around each message
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+// What a vision encoder typically spends on one image (LLaVA uses 576 patches)
+const IMAGE_TOKENS: usize = 576;
+const MAX_TOKEN_RATIO: f64 = 4.0;
+// Room kept for the rolling summary once messages are dropped
+const SUMMARY_TOKENS: usize = 512;
+// Only the start of each dropped message goes into the summary request
+const MAX_SUMMARY_EXCERPT_CHARS: usize = 2000;
+
+const SUMMARY_PROMPT: &str = "Summarize the conversation below so the summary can stand in for it. \
+    Keep names, facts, decisions, open questions and anything the user asked to remember. \
+    Reply with the summary only.";
+
+/// What to leave out when a conversation no longer fits the model's context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextPolicy {
+    /// Drop the oldest messages, system prompts included.
+    #[default]
+    SlidingWindow,
+    /// Keep every system message and as many recent messages as fit.
+    SystemAndRecent,
+    /// Like `SystemAndRecent`, with the dropped messages condensed into a
+    /// summary by the model and stored for later requests.
+    Summary,
+}
+
+impl ContextPolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ContextPolicy::SlidingWindow => "sliding_window",
+            ContextPolicy::SystemAndRecent => "system_and_recent",
+            ContextPolicy::Summary => "summary",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<ContextPolicy> {
+        match value {
+            "sliding_window" => Some(ContextPolicy::SlidingWindow),
+            "system_and_recent" => Some(ContextPolicy::SystemAndRecent),
+            "summary" => Some(ContextPolicy::Summary),
+            _ => None,
+        }
+    }
+}
+
+/// How a request was fitted into the model's context, for the frontend.
+#[derive(Debug, Clone, Serialize)]
+pub struct ContextWindow {
+    pub policy: ContextPolicy,
+    /// The model's context length in tokens.
+    pub context_tokens: usize,
+    /// Estimated prompt tokens of what was sent.
+    pub estimated_tokens: usize,
+    /// Stored messages left out of the prompt, oldest first.
+    pub dropped_message_ids: Vec<i32>,
+    /// Every message left out, including ones not in the database.
+    pub dropped_count: usize,
+    /// The dropped messages were replaced by a summary.
+    pub summarized: bool,
+    /// Why summarizing failed; the dropped messages were left out without one.
+    pub summary_error: Option<String>,
+}
+
+/// A message on its way to the model, with its id when it is stored.
+pub struct ContextMessage {
+    pub id: Option<i32>,
+    pub message: ChatMessage,
+}
+
+pub struct Fitted {
+    pub messages: Vec<ChatMessage>,
+    /// Left out, in conversation order.
+    pub dropped: Vec<ContextMessage>,
+    pub estimated_tokens: usize,
+}
+
+/// Estimates tokens from characters, scaled by how the model counted an
+/// earlier prompt of the same conversation.
+pub struct TokenEstimator {
+    ratio: f64,
+}
+
+impl TokenEstimator {
+    pub fn uncalibrated() -> Self {
+        TokenEstimator { ratio: 1.0 }
+    }
+
+    /// Calibrates against the latest reply in `history` that reported its
+    /// `prompt_eval_count`, whose prompt was the messages before it. Only
+    /// corrections upwards are taken: Ollama leaves prompt prefixes it had
+    /// cached out of the count, so a low count says nothing.
+    pub fn calibrated(history: &[Message]) -> Self {
+        let feedback = history.iter().enumerate().rev().find_map(|(index, message)| {
+            Some((index, message.metrics.as_ref()?.prompt_eval_count?))
+        });
+        let Some((index, counted)) = feedback else {
+            return Self::uncalibrated();
+        };
+
+        let estimated: usize = history[..index].iter()
+            .map(|message| raw_estimate(message.content.chars().count(), message.attachments.len()))
+            .sum();
+        if estimated == 0 || counted <= 0 {
+            return Self::uncalibrated();
+        }
+        TokenEstimator { ratio: (counted as f64 / estimated as f64).clamp(1.0, MAX_TOKEN_RATIO) }
+    }
+
+    pub fn estimate(&self, message: &ChatMessage) -> usize {
+        let images = message.images.as_ref().map_or(0, Vec::len);
+        let raw = raw_estimate(message.content.chars().count(), images);
+        (raw as f64 * self.ratio).ceil() as usize
+    }
+}
+
+fn raw_estimate(chars: usize, images: usize) -> usize {
+    chars.div_ceil(CHARS_PER_TOKEN) + MESSAGE_OVERHEAD_TOKENS + images * IMAGE_TOKENS
+}
+
+/// The model's context length and the part of it the prompt may use; the
+/// rest is left for the reply.
+pub fn prompt_budget(options: Option<&GenerationOptions>) -> (usize, usize) {
+    let context_tokens = options
+        .and_then(|options| options.num_ctx)
+        .map_or(DEFAULT_CONTEXT_TOKENS, |num_ctx| num_ctx as usize);
+    let reply_tokens = options
+        .and_then(|options| options.num_predict)
+        .and_then(|limit| usize::try_from(limit).ok())
+        .filter(|limit| *limit > 0)
+        .map_or(context_tokens / 4, |limit| limit.min(context_tokens / 2));
+    (context_tokens, context_tokens - reply_tokens)
+}
+
+/// Pairs caller-supplied messages with the stored branch they were built
+/// from, matching role and content in order, so dropped messages can be
+/// reported by id. Messages the database does not have, such as a persona
+/// prompt or the turn being sent, get no id.
+pub fn match_stored(messages: Vec<ChatMessage>, stored: &[Message]) -> Vec<ContextMessage> {
+    let mut next = 0;
+    messages.into_iter()
+        .map(|message| {
+            let id = stored.get(next)
+                .filter(|candidate| candidate.role == message.role && candidate.content == message.content)
+                .map(|candidate| candidate.id);
+            if id.is_some() {
+                next += 1;
+            }
+            ContextMessage { id, message }
+        })
+        .collect()
+}
+
+/// Chooses which messages fit in `budget` tokens under `policy`. The last
+/// message, the one being answered, is always kept; otherwise the newest
+/// messages win, and what is left out is a run of the oldest.
+pub fn fit(messages: Vec<ContextMessage>, policy: ContextPolicy, budget: usize, estimator: &TokenEstimator) -> Fitted {
+    let tokens: Vec<usize> = messages.iter().map(|entry| estimator.estimate(&entry.message)).collect();
+    let pinned: Vec<bool> = messages.iter()
+        .map(|entry| policy != ContextPolicy::SlidingWindow && entry.message.role == "system")
+        .collect();
+
+    let mut keep = choose(&tokens, &pinned, budget);
+    if policy == ContextPolicy::Summary && keep.contains(&false) {
+        keep = choose(&tokens, &pinned, budget.saturating_sub(SUMMARY_TOKENS.min(budget / 4)));
+    }
+
+    let mut fitted = Fitted { messages: Vec::new(), dropped: Vec::new(), estimated_tokens: 0 };
+    for ((entry, kept), tokens) in messages.into_iter().zip(keep).zip(tokens) {
+        if kept {
+            fitted.estimated_tokens += tokens;
+            fitted.messages.push(entry.message);
+        } else {
+            fitted.dropped.push(entry);
+        }
+    }
+    fitted
+}
+
+fn choose(tokens: &[usize], pinned: &[bool], budget: usize) -> Vec<bool> {
+    let mut keep = pinned.to_vec();
+    let mut remaining = budget.saturating_sub(
+        tokens.iter().zip(pinned).filter(|(_, pinned)| **pinned).map(|(tokens, _)| tokens).sum(),
+    );
+
+    let mut last = true;
+    for index in (0..tokens.len()).rev().filter(|index| !pinned[*index]) {
+        if !last && tokens[index] > remaining {
+            break;
+        }
+        remaining = remaining.saturating_sub(tokens[index]);
+        keep[index] = true;
+        last = false;
+    }
+    keep
+}
+
+/// Puts the summary of dropped messages after the leading system messages.
+pub fn with_summary(mut messages: Vec<ChatMessage>, summary: &str) -> Vec<ChatMessage> {
+    let position = messages.iter().take_while(|message| message.role == "system").count();
+    messages.insert(position, ChatMessage {
+        role: "system".to_string(),
+        content: format!("Summary of the earlier conversation:\n{}", summary),
+        ..Default::default()
+    });
+    messages
+}
+
+/// Options for a summary request: the conversation's context length, with
+/// the summary kept to the room `with_summary` is given.
+pub fn summary_options(options: Option<&GenerationOptions>) -> GenerationOptions {
+    GenerationOptions {
+        num_ctx: options.and_then(|options| options.num_ctx),
+        num_predict: Some(SUMMARY_TOKENS as i32),
+        ..Default::default()
+    }
+}
+
+/// Splits `dropped` into runs whose summary request, with a previous
+/// summary to extend, fits in `budget` tokens, so the conversation can be
+/// summarized a run at a time. A run holds at least one message.
+pub fn summary_batches(dropped: &[ChatMessage], budget: usize, estimator: &TokenEstimator) -> Vec<Range<usize>> {
+    let fixed = raw_estimate(SUMMARY_PROMPT.chars().count(), 0) + MESSAGE_OVERHEAD_TOKENS + SUMMARY_TOKENS;
+    let room = budget.saturating_sub(fixed);
+
+    let mut batches: Vec<Range<usize>> = Vec::new();
+    let mut used = 0;
+    for (index, message) in dropped.iter().enumerate() {
+        let chars = message.content.chars().count().min(MAX_SUMMARY_EXCERPT_CHARS) + speaker(&message.role).len() + 4;
+        let tokens = (chars.div_ceil(CHARS_PER_TOKEN) as f64 * estimator.ratio).ceil() as usize;
+        match batches.last_mut() {
+            Some(batch) if used + tokens <= room => {
+                batch.end = index + 1;
+                used += tokens;
+            }
+            _ => {
+                batches.push(index..index + 1);
+                used = tokens;
+            }
+        }
+    }
+    batches
+}
+
+/// Asks for a summary of `dropped`, extending `previous` when the earlier
+/// part of the conversation was already summarized.
+pub fn summary_request(previous: Option<&str>, dropped: &[ChatMessage]) -> Vec<ChatMessage> {
+    let mut transcript = String::new();
+    if let Some(previous) = previous {
+        transcript.push_str(&format!("Summary so far:\n{}\n\n", previous));
+    }
+    for message in dropped {
+        let content: String = message.content.chars().take(MAX_SUMMARY_EXCERPT_CHARS).collect();
+        transcript.push_str(&format!("{}: {}\n\n", speaker(&message.role), content.trim()));
+    }
+
+    vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: SUMMARY_PROMPT.to_string(),
+            ..Default::default()
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: transcript.trim_end().to_string(),
+            ..Default::default()
+        },
+    ]
+}
+
+fn speaker(role: &str) -> &str {
+    match role {
+        "user" => "User",
+        "assistant" => "Assistant",
+        "system" => "System",
+        "tool" => "Tool",
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ollama::ReplyMetrics;
+
+    // 36 characters estimate to 13 tokens uncalibrated
+    fn chat(role: &str, chars: usize) -> ContextMessage {
+        ContextMessage {
+            id: None,
+            message: ChatMessage { role: role.to_string(), content: "x".repeat(chars), ..Default::default() },
+        }
+    }
+
+    fn stored(id: i32, role: &str, content: &str, prompt_eval_count: Option<i64>) -> Message {
+        Message {
+            id,
+            conversation_id: 1,
+            role: role.to_string(),
+            content: content.to_string(),
+            reasoning: None,
+            input_type: "text".to_string(),
+            attachments: Vec::new(),
+            timestamp: "2024-01-01 00:00:00".to_string(),
+            status: "complete".to_string(),
+            tool_calls: None,
+            tool_name: None,
+            structured_content: None,
+            model: None,
+            metrics: prompt_eval_count.map(|count| ReplyMetrics { prompt_eval_count: Some(count), ..Default::default() }),
+            parent_id: None,
+            sibling_index: 1,
+            sibling_count: 1,
+        }
+    }
+
+    fn roles(messages: &[ChatMessage]) -> Vec<&str> {
+        messages.iter().map(|message| message.role.as_str()).collect()
+    }
+
+    #[test]
+    fn pins_system_messages_unless_sliding() {
+        let conversation = || vec![chat("system", 36), chat("user", 36), chat("assistant", 36), chat("user", 36)];
+        let estimator = TokenEstimator::uncalibrated();
+
+        let fitted = fit(conversation(), ContextPolicy::SystemAndRecent, 40, &estimator);
+        assert_eq!(roles(&fitted.messages), ["system", "assistant", "user"]);
+        assert_eq!(fitted.dropped.len(), 1);
+        assert_eq!(fitted.estimated_tokens, 39);
+
+        let fitted = fit(conversation(), ContextPolicy::SlidingWindow, 40, &estimator);
+        assert_eq!(roles(&fitted.messages), ["user", "assistant", "user"]);
+        assert_eq!(fitted.dropped[0].message.role, "system");
+    }
+
+    #[test]
+    fn keeps_the_last_message_when_it_alone_is_over_budget() {
+        let fitted = fit(vec![chat("user", 36), chat("user", 400)], ContextPolicy::SystemAndRecent, 50, &TokenEstimator::uncalibrated());
+        assert_eq!(fitted.messages.len(), 1);
+        assert_eq!(fitted.messages[0].content.len(), 400);
+        assert_eq!(fitted.estimated_tokens, 104);
+        assert_eq!(fitted.dropped.len(), 1);
+    }
+
+    #[test]
+    fn leaves_room_for_the_summary_once_messages_are_dropped() {
+        // 104 tokens each, 2080 in all
+        let conversation = |count| (0..count).map(|_| chat("user", 400)).collect::<Vec<_>>();
+        let estimator = TokenEstimator::uncalibrated();
+
+        let fitted = fit(conversation(20), ContextPolicy::SystemAndRecent, 2048, &estimator);
+        assert_eq!(fitted.dropped.len(), 1);
+
+        let fitted = fit(conversation(20), ContextPolicy::Summary, 2048, &estimator);
+        assert_eq!(fitted.dropped.len(), 6);
+        assert!(fitted.estimated_tokens <= 2048 - SUMMARY_TOKENS);
+
+        let fitted = fit(conversation(19), ContextPolicy::Summary, 2048, &estimator);
+        assert!(fitted.dropped.is_empty());
+    }
+
+    #[test]
+    fn matches_stored_messages_by_role_and_content() {
+        let history = [stored(1, "user", "Hi", None), stored(2, "assistant", "Hello", None)];
+        let messages = [("system", "Be brief"), ("user", "Hi"), ("assistant", "Hello"), ("user", "Hi")]
+            .into_iter()
+            .map(|(role, content)| ChatMessage { role: role.to_string(), content: content.to_string(), ..Default::default() })
+            .collect();
+
+        let ids: Vec<Option<i32>> = match_stored(messages, &history).iter().map(|entry| entry.id).collect();
+        assert_eq!(ids, [None, Some(1), Some(2), None]);
+    }
+
+    #[test]
+    fn calibrates_upwards_only_and_within_bounds() {
+        let message = chat("user", 36).message;
+        let estimate = |prompt_eval_count| {
+            let history = [stored(1, "user", &"x".repeat(36), None), stored(2, "assistant", "4", prompt_eval_count)];
+            TokenEstimator::calibrated(&history).estimate(&message)
+        };
+
+        assert_eq!(estimate(None), 13);
+        assert_eq!(estimate(Some(26)), 26);
+        assert_eq!(estimate(Some(5)), 13);
+        assert_eq!(estimate(Some(1000)), 52);
+    }
+
+    #[test]
+    fn batches_dropped_messages_to_fit_the_summary_request() {
+        let fixed = raw_estimate(SUMMARY_PROMPT.chars().count(), 0) + MESSAGE_OVERHEAD_TOKENS + SUMMARY_TOKENS;
+        // "User: " and the separator make each of these 10 tokens
+        let dropped: Vec<ChatMessage> = [32, 32, 32, 400, 32].into_iter().map(|chars| chat("user", chars).message).collect();
+
+        let batches = summary_batches(&dropped, fixed + 30, &TokenEstimator::uncalibrated());
+        assert_eq!(batches, [0..3, 3..4, 4..5]);
+    }
+}
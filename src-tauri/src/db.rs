@@ -1,12 +1,46 @@
-use rusqlite::{Connection, Result, params};
+use rusqlite::{Connection, DatabaseName, Result, params};
+use rusqlite::backup::Backup;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use base64::{Engine as _, engine::general_purpose};
 use dirs;
+use crate::encryption::{self, EncryptionStatus, ImageKey, IMAGE_KEY_SETTING};
+use crate::context::ContextPolicy;
+use crate::migrations;
+use crate::ollama::{ChatMessage, GenerationOptions, ReplyMetrics};
+use crate::provider::ProviderKind;
+use std::collections::BTreeMap;
+use std::time::Duration;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Conversation {
     pub id: i32,
     pub created_at: String,
+    pub title: Option<String>,
+    /// Time of the latest message, or `created_at` for an empty conversation.
+    pub updated_at: String,
+    /// Model of the latest message that recorded one.
+    pub model: Option<String>,
+    /// Last message of the branch being shown; see `Database::get_messages`.
+    pub active_leaf_id: Option<i32>,
+    pub endpoint_id: Option<i32>,
+    /// Protocol of the endpoint that last answered.
+    pub provider: Option<ProviderKind>,
+    pub persona_id: Option<i32>,
+    /// Unset means `ContextPolicy::default()`.
+    pub context_policy: Option<ContextPolicy>,
+}
+
+/// Rolling summary of the messages dropped from a conversation's context.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationSummary {
+    pub conversation_id: i32,
+    /// Last message the summary covers.
+    pub through_message_id: i32,
+    pub summary: String,
+    pub model: Option<String>,
+    pub created_at: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -15,15 +49,167 @@ pub struct Message {
     pub conversation_id: i32,
     pub role: String,
     pub content: String,
+    /// Reasoning a thinking model gave before its answer; not part of `content`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
     pub input_type: String,
-    pub image_path: Option<String>,
-    pub image_filename: Option<String>,
-    pub image_size: Option<i32>,
+    /// Images sent with the message, in the order they were attached.
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
     pub timestamp: String,
+    pub status: String,
+    pub tool_calls: Option<serde_json::Value>,
+    pub tool_name: Option<String>,
+    pub structured_content: Option<serde_json::Value>,
+    pub model: Option<String>,
+    /// Token counts and timings for an assistant reply, when the server reported them.
+    pub metrics: Option<ReplyMetrics>,
+    /// The message this one follows; `None` for the first message of a branch root.
+    pub parent_id: Option<i32>,
+    /// 1-based position among the messages sharing `parent_id`, for branch navigation.
+    pub sibling_index: i32,
+    pub sibling_count: i32,
+}
+
+impl Message {
+    pub fn to_chat_message(&self) -> ChatMessage {
+        ChatMessage {
+            role: self.role.clone(),
+            content: self.content.clone(),
+            tool_calls: self.tool_calls.clone().and_then(|calls| serde_json::from_value(calls).ok()),
+            tool_name: self.tool_name.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub path: String,
+    pub filename: Option<String>,
+    pub size: Option<i32>,
+}
+
+/// Narrows a full-text search. Dates compare against message timestamps
+/// (`YYYY-MM-DD` or `YYYY-MM-DD HH:MM:SS`).
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SearchFilters {
+    pub role: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub model: Option<String>,
+    pub has_image: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    pub message: Message,
+    pub conversation: Conversation,
+    /// Matching excerpt, HTML-escaped, with hits wrapped in `<mark>` tags.
+    pub snippet: String,
+    pub rank: f64,
+}
+
+/// Token usage of one model's replies over a date range.
+#[derive(Debug, Serialize)]
+pub struct ModelUsage {
+    pub model: String,
+    pub replies: i64,
+    /// Replies with recorded token counts; older replies have none.
+    pub measured_replies: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    /// Nanoseconds, summed over the measured replies.
+    pub total_duration: i64,
+    /// Generated tokens per second of generation time, over the replies
+    /// that reported both.
+    pub tokens_per_second: Option<f64>,
+}
+
+pub const MESSAGE_STATUS_COMPLETE: &str = "complete";
+pub const MESSAGE_STATUS_CANCELLED: &str = "cancelled";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Endpoint {
+    pub id: i32,
+    pub name: String,
+    pub base_url: String,
+    pub provider: ProviderKind,
+    /// Sent as a bearer token.
+    pub api_key: Option<String>,
+    /// Extra HTTP headers sent with every request.
+    pub headers: BTreeMap<String, String>,
+    pub created_at: String,
+    pub is_selected: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Persona {
+    pub id: i32,
+    pub name: String,
+    pub system_prompt: String,
+    pub default_model: Option<String>,
+    pub default_options: Option<GenerationOptions>,
+    pub created_at: String,
+}
+
+// Generation options are kept as JSON text columns
+impl ToSql for GenerationOptions {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        serde_json::to_string(self)
+            .map(ToSqlOutput::from)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+    }
+}
+
+impl FromSql for GenerationOptions {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        serde_json::from_str(value.as_str()?).map_err(|e| FromSqlError::Other(Box::new(e)))
+    }
+}
+
+impl ToSql for ProviderKind {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for ProviderKind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let value = value.as_str()?;
+        ProviderKind::parse(value).ok_or_else(|| FromSqlError::Other(format!("Unknown provider: {}", value).into()))
+    }
+}
+
+impl ToSql for ContextPolicy {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for ContextPolicy {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let value = value.as_str()?;
+        ContextPolicy::parse(value).ok_or_else(|| FromSqlError::Other(format!("Unknown context policy: {}", value).into()))
+    }
 }
 
+// Pages copied per step of an online backup
+const BACKUP_PAGES_PER_STEP: i32 = 1024;
+
+pub const DEFAULT_ENDPOINT_URL: &str = "http://localhost:11434";
+pub const SELECTED_ENDPOINT_KEY: &str = "selected_endpoint_id";
+
 pub struct Database {
     conn: Connection,
+    /// Whether `chat.db` is encrypted with SQLCipher.
+    encrypted: bool,
+    /// Key of the open encrypted database, for keying backup snapshots.
+    passphrase: Option<String>,
+    /// Set while the database is encrypted and no passphrase has been given;
+    /// nothing can be read until `unlock` succeeds.
+    locked: bool,
+    image_key: Option<ImageKey>,
 }
 
 impl Database {
@@ -39,90 +225,459 @@ impl Database {
             })?;
         }
 
-        let conn = Connection::open(&db_path)?;
-        let db = Database { conn };
-        db.init_tables()?;
-        Ok(db)
+        match open_connection(&db_path, None)? {
+            Some(conn) => Database::ready(conn, &db_path, None),
+            None => Ok(Database {
+                conn: Connection::open(&db_path)?,
+                encrypted: true,
+                passphrase: None,
+                locked: true,
+                image_key: None,
+            }),
+        }
     }
 
-    fn init_tables(&self) -> Result<()> {
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS conversations (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                created_at TEXT NOT NULL
-            )",
-            [],
-        )?;
+    fn ready(mut conn: Connection, db_path: &Path, passphrase: Option<&str>) -> Result<Self> {
+        migrations::run(&mut conn, db_path)?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        let image_key = if passphrase.is_some() { load_image_key(&conn)? } else { None };
+        Ok(Database {
+            conn,
+            encrypted: passphrase.is_some(),
+            passphrase: passphrase.map(str::to_string),
+            locked: false,
+            image_key,
+        })
+    }
+
+    pub fn encryption_status(&self) -> EncryptionStatus {
+        EncryptionStatus { encrypted: self.encrypted, locked: self.locked }
+    }
+
+    /// Key for image files, set once an encrypted database is unlocked.
+    pub fn image_key(&self) -> Option<&ImageKey> {
+        self.image_key.as_ref()
+    }
+
+    /// Opens the encrypted database with `passphrase`, migrating it if
+    /// needed. Returns `false` when the passphrase is wrong.
+    pub fn unlock(&mut self, passphrase: &str) -> Result<bool> {
+        if !self.locked {
+            return Ok(true);
+        }
+        let db_path = get_db_path();
+        let Some(conn) = open_connection(&db_path, Some(passphrase))? else {
+            return Ok(false);
+        };
+        *self = Database::ready(conn, &db_path, Some(passphrase))?;
+        Ok(true)
+    }
+
+    /// Rewrites the plain database as an SQLCipher copy keyed with
+    /// `passphrase`, with a fresh image key stored inside it, and swaps the
+    /// copy in. Existing images are left for the caller to encrypt.
+    pub fn enable_encryption(&mut self, passphrase: &str) -> Result<()> {
+        if self.encrypted {
+            return Err(migrations::failure("The database is already encrypted.".to_string()));
+        }
+        let db_path = get_db_path();
+        let encrypted_path = db_path.with_extension("db.encrypting");
+        let _ = std::fs::remove_file(&encrypted_path);
 
+        let version = self.schema_version()?;
+        let image_key = general_purpose::STANDARD.encode(encryption::generate_image_key());
         self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS messages (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                conversation_id INTEGER,
-                role TEXT NOT NULL,
-                content TEXT NOT NULL,
-                input_type TEXT NOT NULL DEFAULT 'text',
-                image_path TEXT,
-                image_filename TEXT,
-                image_size INTEGER,
-                timestamp TEXT NOT NULL,
-                FOREIGN KEY (conversation_id) REFERENCES conversations(id)
-            )",
-            [],
+            "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+            params![encrypted_path.to_string_lossy(), passphrase],
         )?;
+        // sqlcipher_export copies the schema and rows but not user_version
+        let exported = self.conn.query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()))
+            .and_then(|_| self.conn.pragma_update(Some(DatabaseName::Attached("encrypted")), "user_version", version))
+            .and_then(|_| self.conn.execute(
+                "INSERT INTO encrypted.settings (key, value) VALUES (?1, ?2)
+                 ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+                params![IMAGE_KEY_SETTING, image_key],
+            ));
+        self.conn.execute("DETACH DATABASE encrypted", [])?;
+        if let Err(e) = exported {
+            let _ = std::fs::remove_file(&encrypted_path);
+            return Err(e);
+        }
+
+        let replaced = self.replace_file(&encrypted_path, Some(passphrase));
+        if replaced.is_err() {
+            let _ = std::fs::remove_file(&encrypted_path);
+        }
+        replaced
+    }
+
+    /// Re-keys the encrypted database. Returns `false` when `current` is wrong.
+    pub fn change_passphrase(&mut self, current: &str, new: &str) -> Result<bool> {
+        if !self.encrypted || self.locked {
+            return Err(migrations::failure("The database is not unlocked.".to_string()));
+        }
+        if open_connection(&get_db_path(), Some(current))?.is_none() {
+            return Ok(false);
+        }
+        self.conn.pragma_update(None, "rekey", new)?;
+        self.passphrase = Some(new.to_string());
+        Ok(true)
+    }
+
+    pub fn schema_version(&self) -> Result<i32> {
+        self.conn.pragma_query_value(None, "user_version", |row| row.get(0))
+    }
 
-        // Add new columns to existing messages table if they don't exist
-        let _ = self.conn.execute("ALTER TABLE messages ADD COLUMN input_type TEXT DEFAULT 'text'", []);
-        let _ = self.conn.execute("ALTER TABLE messages ADD COLUMN image_path TEXT", []);
-        let _ = self.conn.execute("ALTER TABLE messages ADD COLUMN image_filename TEXT", []);
-        let _ = self.conn.execute("ALTER TABLE messages ADD COLUMN image_size INTEGER", []);
+    /// Writes a consistent copy of the live database to `path`, which must
+    /// not exist yet, with SQLite's online backup. An encrypted database is
+    /// copied with the same key.
+    pub fn backup_to(&self, path: &Path) -> Result<()> {
+        let mut snapshot = Connection::open(path)?;
+        if let Some(passphrase) = &self.passphrase {
+            snapshot.pragma_update(None, "key", passphrase)?;
+        }
+        let backup = Backup::new(&self.conn, &mut snapshot)?;
+        backup.run_to_completion(BACKUP_PAGES_PER_STEP, Duration::ZERO, None)
+    }
+
+    /// Replaces the live database with the file at `path`, keyed with
+    /// `passphrase` if it is encrypted, then migrates it to the current
+    /// schema. The file is moved, not copied; on failure it is left at
+    /// `path` and the current database stays open.
+    pub fn restore_from(&mut self, path: &Path, passphrase: Option<&str>) -> Result<()> {
+        self.replace_file(path, passphrase)
+    }
+
+    // The live file is moved aside rather than overwritten, so on any failure
+    // both files go back where they were and the old database is reopened.
+    fn replace_file(&mut self, source: &Path, passphrase: Option<&str>) -> Result<()> {
+        let db_path = get_db_path();
+        let previous_path = db_path.with_extension("db.replacing");
+        // Close the live connection first; Windows cannot replace an open file
+        self.conn = Connection::open_in_memory()?;
+        if let Err(e) = std::fs::rename(&db_path, &previous_path) {
+            self.reopen(&db_path)?;
+            return Err(migrations::failure(format!("Failed to move the current database aside: {}", e)));
+        }
+        if let Err(e) = std::fs::rename(source, &db_path) {
+            let _ = std::fs::rename(&previous_path, &db_path);
+            self.reopen(&db_path)?;
+            return Err(migrations::failure(format!("Failed to replace database file: {}", e)));
+        }
+
+        let replaced = open_connection(&db_path, passphrase)
+            .and_then(|conn| conn.ok_or_else(|| migrations::failure("The new database cannot be opened with this passphrase.".to_string())))
+            .and_then(|conn| Database::ready(conn, &db_path, passphrase));
+        match replaced {
+            Ok(database) => {
+                *self = database;
+                let _ = std::fs::remove_file(&previous_path);
+                Ok(())
+            }
+            Err(e) => {
+                let _ = std::fs::rename(&db_path, source);
+                let _ = std::fs::rename(&previous_path, &db_path);
+                self.reopen(&db_path)?;
+                Err(e)
+            }
+        }
+    }
 
+    // Reconnects to the file this struct describes after a failed replace
+    fn reopen(&mut self, db_path: &Path) -> Result<()> {
+        let conn = open_connection(db_path, self.passphrase.as_deref())?
+            .ok_or_else(|| migrations::failure("The database could not be reopened.".to_string()))?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        self.conn = conn;
         Ok(())
     }
 
     pub fn create_conversation(&self, created_at: &str) -> Result<i32> {
         self.conn.execute(
-            "INSERT INTO conversations (created_at) VALUES (?1)",
+            "INSERT INTO conversations (created_at, updated_at) VALUES (?1, ?1)",
             params![created_at],
         )?;
         Ok(self.conn.last_insert_rowid() as i32)
     }
 
-    pub fn save_message(&self, conversation_id: i32, role: &str, content: &str, timestamp: &str) -> Result<()> {
+    pub fn save_message(
+        &self,
+        conversation_id: i32,
+        role: &str,
+        content: &str,
+        model: Option<&str>,
+        metrics: Option<&ReplyMetrics>,
+        timestamp: &str
+    ) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO messages (conversation_id, role, content, input_type, model, timestamp) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![conversation_id, role, content, "text", model, timestamp],
+        )?;
+        self.set_message_metrics(self.conn.last_insert_rowid() as i32, metrics)
+    }
+
+    pub fn save_message_with_status(
+        &self,
+        conversation_id: i32,
+        message: &ChatMessage,
+        status: &str,
+        model: Option<&str>,
+        timestamp: &str
+    ) -> Result<()> {
         self.conn.execute(
-            "INSERT INTO messages (conversation_id, role, content, input_type, timestamp) VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![conversation_id, role, content, "text", timestamp],
+            "INSERT INTO messages (conversation_id, role, content, reasoning, input_type, model, timestamp, status) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![conversation_id, message.role, message.content, message.thinking, "text", model, timestamp, status],
         )?;
         Ok(())
     }
 
-    pub fn save_message_with_image(
-        &self, 
-        conversation_id: i32, 
-        role: &str, 
-        content: &str, 
-        input_type: &str,
-        image_path: Option<&str>,
-        image_filename: Option<&str>,
-        image_size: Option<i32>,
+    /// Stores a message as the model exchange produced it: a reply with its
+    /// reasoning, an assistant turn that may request `tool_calls`, or a
+    /// `tool` turn carrying a result.
+    pub fn save_chat_message(
+        &self,
+        conversation_id: i32,
+        message: &ChatMessage,
+        model: Option<&str>,
+        metrics: Option<&ReplyMetrics>,
+        timestamp: &str
+    ) -> Result<()> {
+        let tool_calls = message.tool_calls.as_ref()
+            .filter(|calls| !calls.is_empty())
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        self.conn.execute(
+            "INSERT INTO messages (conversation_id, role, content, reasoning, input_type, model, timestamp, tool_calls, tool_name) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![conversation_id, message.role, message.content, message.thinking, "text", model, timestamp, tool_calls, message.tool_name],
+        )?;
+        self.set_message_metrics(self.conn.last_insert_rowid() as i32, metrics)
+    }
+
+    /// Stores an assistant reply that passed schema validation, keeping the
+    /// parsed JSON alongside the raw text.
+    pub fn save_structured_message(
+        &self,
+        conversation_id: i32,
+        reply: &ChatMessage,
+        structured_content: &str,
+        model: &str,
+        metrics: Option<&ReplyMetrics>,
         timestamp: &str
     ) -> Result<()> {
         self.conn.execute(
-            "INSERT INTO messages (conversation_id, role, content, input_type, image_path, image_filename, image_size, timestamp) 
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-            params![conversation_id, role, content, input_type, image_path, image_filename, image_size, timestamp],
+            "INSERT INTO messages (conversation_id, role, content, reasoning, input_type, model, timestamp, structured_content) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![conversation_id, "assistant", reply.content, reply.thinking, "text", model, timestamp, structured_content],
+        )?;
+        self.set_message_metrics(self.conn.last_insert_rowid() as i32, metrics)
+    }
+
+    pub fn set_message_reasoning(&self, id: i32, reasoning: Option<&str>) -> Result<()> {
+        self.conn.execute(
+            "UPDATE messages SET reasoning = ?1 WHERE id = ?2",
+            params![reasoning, id],
         )?;
         Ok(())
     }
 
-    pub fn get_conversations(&self) -> Result<Vec<Conversation>> {
-        let mut stmt = self.conn.prepare("SELECT id, created_at FROM conversations ORDER BY created_at DESC")?;
-        let conversation_iter = stmt.query_map([], |row| {
-            Ok(Conversation {
-                id: row.get(0)?,
-                created_at: row.get(1)?,
+    /// Stores the token counts and timings reported for a reply.
+    pub fn set_message_metrics(&self, id: i32, metrics: Option<&ReplyMetrics>) -> Result<()> {
+        let Some(metrics) = metrics.filter(|metrics| !metrics.is_empty()) else {
+            return Ok(());
+        };
+        self.conn.execute(
+            "UPDATE messages SET total_duration = ?1, load_duration = ?2, prompt_eval_count = ?3,
+                eval_count = ?4, eval_duration = ?5, model_digest = ?6
+             WHERE id = ?7",
+            params![
+                metrics.total_duration,
+                metrics.load_duration,
+                metrics.prompt_eval_count,
+                metrics.eval_count,
+                metrics.eval_duration,
+                metrics.model_digest,
+                id
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Totals token usage per model over assistant replies timestamped
+    /// between `from` and `to` inclusive (`YYYY-MM-DD` or
+    /// `YYYY-MM-DD HH:MM:SS`; see `search::end_of_day` for a whole last
+    /// day), busiest model first.
+    pub fn get_usage_stats(&self, from: Option<&str>, to: Option<&str>) -> Result<Vec<ModelUsage>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT model, COUNT(*), COUNT(eval_count),
+                    COALESCE(SUM(prompt_eval_count), 0), COALESCE(SUM(eval_count), 0),
+                    COALESCE(SUM(total_duration), 0),
+                    SUM(CASE WHEN eval_duration > 0 THEN eval_count END),
+                    SUM(CASE WHEN eval_count IS NOT NULL THEN eval_duration END)
+             FROM messages
+             WHERE role = 'assistant' AND model IS NOT NULL
+               AND (?1 IS NULL OR timestamp >= ?1)
+               AND (?2 IS NULL OR timestamp <= ?2)
+             GROUP BY model
+             ORDER BY COUNT(*) DESC, model ASC"
+        )?;
+        let rows = stmt.query_map(params![from, to], |row| {
+            let timed_tokens: Option<i64> = row.get(6)?;
+            let eval_duration: Option<i64> = row.get(7)?;
+            Ok(ModelUsage {
+                model: row.get(0)?,
+                replies: row.get(1)?,
+                measured_replies: row.get(2)?,
+                prompt_tokens: row.get(3)?,
+                completion_tokens: row.get(4)?,
+                total_duration: row.get(5)?,
+                tokens_per_second: match (timed_tokens, eval_duration) {
+                    (Some(tokens), Some(duration)) if duration > 0 => Some(tokens as f64 / (duration as f64 / 1e9)),
+                    _ => None,
+                },
             })
         })?;
 
+        rows.collect()
+    }
+
+    /// Saves a message with its images and returns its id.
+    pub fn save_message_with_attachments(
+        &self,
+        conversation_id: i32,
+        role: &str,
+        content: &str,
+        input_type: &str,
+        attachments: &[Attachment],
+        timestamp: &str
+    ) -> Result<i32> {
+        self.in_transaction(|db| {
+            db.conn.execute(
+                "INSERT INTO messages (conversation_id, role, content, input_type, timestamp) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![conversation_id, role, content, input_type, timestamp],
+            )?;
+            let id = db.conn.last_insert_rowid() as i32;
+            for (position, attachment) in attachments.iter().enumerate() {
+                db.conn.execute(
+                    "INSERT INTO attachments (message_id, position, path, filename, size) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![id, position as i64, attachment.path, attachment.filename, attachment.size],
+                )?;
+            }
+            Ok(id)
+        })
+    }
+
+    /// Sets the fields `save_message_with_attachments` does not take, for messages
+    /// restored from elsewhere, and records the model on the conversation.
+    pub fn restore_message_fields(
+        &self,
+        id: i32,
+        status: &str,
+        model: Option<&str>,
+        tool_calls: Option<&str>,
+        tool_name: Option<&str>,
+        structured_content: Option<&str>,
+    ) -> Result<()> {
+        self.conn.execute(
+            "UPDATE messages SET status = ?1, model = ?2, tool_calls = ?3, tool_name = ?4, structured_content = ?5
+             WHERE id = ?6",
+            params![status, model, tool_calls, tool_name, structured_content, id],
+        )?;
+        self.conn.execute(
+            "UPDATE conversations SET model = COALESCE(?1, model)
+             WHERE id = (SELECT conversation_id FROM messages WHERE id = ?2)",
+            params![model, id],
+        )?;
+        Ok(())
+    }
+
+    /// Records which endpoint and protocol answered the conversation; with no
+    /// endpoint selected only the protocol is updated.
+    pub fn set_conversation_provider(&self, conversation_id: i32, endpoint_id: Option<i32>, provider: ProviderKind) -> Result<()> {
+        self.conn.execute(
+            "UPDATE conversations SET endpoint_id = COALESCE(?1, endpoint_id), provider = ?2 WHERE id = ?3",
+            params![endpoint_id, provider, conversation_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_context_policy(&self, conversation_id: i32, policy: Option<ContextPolicy>) -> Result<()> {
+        self.conn.execute(
+            "UPDATE conversations SET context_policy = ?1 WHERE id = ?2",
+            params![policy, conversation_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_conversation_summary(&self, conversation_id: i32) -> Result<Option<ConversationSummary>> {
+        let summary = self.conn.query_row(
+            "SELECT conversation_id, through_message_id, summary, model, created_at
+             FROM conversation_summaries WHERE conversation_id = ?1",
+            params![conversation_id],
+            |row| Ok(ConversationSummary {
+                conversation_id: row.get(0)?,
+                through_message_id: row.get(1)?,
+                summary: row.get(2)?,
+                model: row.get(3)?,
+                created_at: row.get(4)?,
+            }),
+        );
+        match summary {
+            Ok(summary) => Ok(Some(summary)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Replaces the conversation's summary; there is one per conversation.
+    pub fn save_conversation_summary(&self, conversation_id: i32, through_message_id: i32, summary: &str, model: &str, created_at: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO conversation_summaries (conversation_id, through_message_id, summary, model, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![conversation_id, through_message_id, summary, model, created_at],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_conversation_options(&self, conversation_id: i32) -> Result<Option<GenerationOptions>> {
+        self.conn.query_row(
+            "SELECT options FROM conversations WHERE id = ?1",
+            params![conversation_id],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn set_conversation_options(&self, conversation_id: i32, options: Option<&GenerationOptions>) -> Result<()> {
+        self.conn.execute(
+            "UPDATE conversations SET options = ?1 WHERE id = ?2",
+            params![options, conversation_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_model_options(&self, model: &str) -> Result<Option<GenerationOptions>> {
+        match self.conn.query_row("SELECT options FROM model_options WHERE model = ?1", params![model], |row| row.get(0)) {
+            Ok(options) => Ok(Some(options)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn set_model_options(&self, model: &str, options: Option<&GenerationOptions>) -> Result<()> {
+        match options {
+            Some(options) => self.conn.execute(
+                "INSERT INTO model_options (model, options) VALUES (?1, ?2)
+                 ON CONFLICT(model) DO UPDATE SET options = excluded.options",
+                params![model, options],
+            )?,
+            None => self.conn.execute("DELETE FROM model_options WHERE model = ?1", params![model])?,
+        };
+        Ok(())
+    }
+
+    pub fn get_conversations(&self) -> Result<Vec<Conversation>> {
+        let mut stmt = self.conn.prepare(&format!("SELECT {} FROM conversations ORDER BY updated_at DESC, id DESC", CONVERSATION_COLUMNS))?;
+        let conversation_iter = stmt.query_map([], conversation_from_row)?;
+
         let mut conversations = Vec::new();
         for conversation in conversation_iter {
             conversations.push(conversation?);
@@ -130,35 +685,488 @@ impl Database {
         Ok(conversations)
     }
 
+    pub fn get_conversation(&self, id: i32) -> Result<Conversation> {
+        self.conn.query_row(
+            &format!("SELECT {} FROM conversations WHERE id = ?1", CONVERSATION_COLUMNS),
+            params![id],
+            conversation_from_row,
+        )
+    }
+
+    pub fn rename_conversation(&self, id: i32, title: Option<&str>) -> Result<()> {
+        self.conn.execute(
+            "UPDATE conversations SET title = ?1 WHERE id = ?2",
+            params![title, id],
+        )?;
+        Ok(())
+    }
+
+    /// Points the active branch at `message_id` (`None` for an empty branch),
+    /// so the next message saved becomes its reply.
+    pub fn set_active_leaf(&self, conversation_id: i32, message_id: Option<i32>) -> Result<()> {
+        self.conn.execute(
+            "UPDATE conversations SET active_leaf_id = ?1 WHERE id = ?2",
+            params![message_id, conversation_id],
+        )?;
+        Ok(())
+    }
+
+    /// Finds a conversation created at `created_at` whose first message
+    /// matches, which is how re-imported history is recognised.
+    pub fn find_matching_conversation(&self, created_at: &str, role: &str, content: &str, timestamp: &str) -> Result<Option<i32>> {
+        match self.conn.query_row(
+            "SELECT c.id FROM conversations c
+             JOIN messages m ON m.id = (SELECT MIN(id) FROM messages WHERE conversation_id = c.id)
+             WHERE c.created_at = ?1 AND m.role = ?2 AND m.content = ?3 AND m.timestamp = ?4
+             LIMIT 1",
+            params![created_at, role, content, timestamp],
+            |row| row.get(0),
+        ) {
+            Ok(id) => Ok(Some(id)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Runs `f` in a transaction, rolling everything back if it fails. A
+    /// savepoint, so it nests inside a transaction `f` or its caller started.
+    pub fn in_transaction<T>(&self, f: impl FnOnce(&Self) -> Result<T>) -> Result<T> {
+        self.conn.execute_batch("SAVEPOINT in_transaction")?;
+        match f(self) {
+            Ok(value) => {
+                self.conn.execute_batch("RELEASE in_transaction")?;
+                Ok(value)
+            }
+            Err(e) => {
+                self.conn.execute_batch("ROLLBACK TO in_transaction; RELEASE in_transaction")?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Stores a generated title unless the conversation already has one, so an
+    /// auto-title never overwrites a rename. Returns whether it was stored.
+    pub fn set_generated_title(&self, id: i32, title: &str) -> Result<bool> {
+        let updated = self.conn.execute(
+            "UPDATE conversations SET title = ?1 WHERE id = ?2 AND title IS NULL",
+            params![title, id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Deletes the conversation; its messages, embeddings and search entries
+    /// go with it through `ON DELETE CASCADE` and the FTS triggers.
+    pub fn delete_conversation(&self, id: i32) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        let deleted = tx.execute("DELETE FROM conversations WHERE id = ?1", params![id])?;
+        if deleted == 0 {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+        tx.commit()
+    }
+
+    /// Deletes a message together with every reply branching from it, and
+    /// returns the image paths no remaining message refers to so the caller
+    /// can remove the files after the commit. If the active branch went with
+    /// it, the newest remaining branch under its parent becomes active.
+    pub fn delete_message(&self, id: i32) -> Result<Vec<String>> {
+        let tx = self.conn.unchecked_transaction()?;
+        let (conversation_id, parent_id): (i32, Option<i32>) = tx.query_row(
+            "SELECT conversation_id, parent_id FROM messages WHERE id = ?1",
+            params![id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+
+        let image_paths: Vec<String> = {
+            let mut stmt = tx.prepare(
+                "WITH RECURSIVE subtree(message_id) AS (
+                     SELECT ?1
+                     UNION ALL
+                     SELECT m.id FROM messages m JOIN subtree ON m.parent_id = subtree.message_id
+                 )
+                 SELECT DISTINCT path FROM attachments
+                 JOIN subtree ON subtree.message_id = attachments.message_id",
+            )?;
+            let paths = stmt.query_map(params![id], |row| row.get(0))?;
+            paths.collect::<Result<_>>()?
+        };
+        tx.execute("DELETE FROM messages WHERE id = ?1", params![id])?;
+
+        let active_leaf_id: Option<i32> = tx.query_row(
+            "SELECT active_leaf_id FROM conversations WHERE id = ?1",
+            params![conversation_id],
+            |row| row.get(0),
+        )?;
+        if active_leaf_id.is_none() {
+            let leaf = latest_leaf(&tx, conversation_id, parent_id)?;
+            tx.execute(
+                "UPDATE conversations SET active_leaf_id = ?1 WHERE id = ?2",
+                params![leaf, conversation_id],
+            )?;
+        }
+
+        let mut orphaned_images = Vec::new();
+        for path in image_paths {
+            let still_used: bool = tx.query_row(
+                "SELECT COUNT(*) > 0 FROM attachments WHERE path = ?1",
+                params![path],
+                |row| row.get(0),
+            )?;
+            if !still_used {
+                orphaned_images.push(path);
+            }
+        }
+        tx.commit()?;
+        Ok(orphaned_images)
+    }
+
+    /// Messages on the conversation's active branch, oldest first.
     pub fn get_messages(&self, conversation_id: i32) -> Result<Vec<Message>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "WITH RECURSIVE branch(message_id, depth) AS (
+                 SELECT active_leaf_id, 0 FROM conversations WHERE id = ?1 AND active_leaf_id IS NOT NULL
+                 UNION ALL
+                 SELECT m.parent_id, branch.depth + 1 FROM messages m
+                 JOIN branch ON m.id = branch.message_id
+                 WHERE m.parent_id IS NOT NULL
+             )
+             SELECT {} FROM messages JOIN branch ON branch.message_id = messages.id
+             ORDER BY branch.depth DESC",
+            MESSAGE_COLUMNS
+        ))?;
+        let message_iter = stmt.query_map(params![conversation_id], message_from_row)?;
+
+        let mut messages = Vec::new();
+        for message in message_iter {
+            messages.push(message?);
+        }
+        Ok(messages)
+    }
+
+    /// Every message in the conversation across all branches, in the order
+    /// they were saved, so parents always come before their replies.
+    pub fn get_all_messages(&self, conversation_id: i32) -> Result<Vec<Message>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM messages WHERE conversation_id = ?1 ORDER BY id ASC",
+            MESSAGE_COLUMNS
+        ))?;
+        let message_iter = stmt.query_map(params![conversation_id], message_from_row)?;
+
+        let mut messages = Vec::new();
+        for message in message_iter {
+            messages.push(message?);
+        }
+        Ok(messages)
+    }
+
+    /// The message and the alternatives sharing its parent, oldest first.
+    pub fn get_message_siblings(&self, id: i32) -> Result<Vec<Message>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM messages
+             WHERE conversation_id = (SELECT conversation_id FROM messages WHERE id = ?1)
+               AND parent_id IS (SELECT parent_id FROM messages WHERE id = ?1)
+             ORDER BY id ASC",
+            MESSAGE_COLUMNS
+        ))?;
+        let message_iter = stmt.query_map(params![id], message_from_row)?;
+
+        let mut messages = Vec::new();
+        for message in message_iter {
+            messages.push(message?);
+        }
+        Ok(messages)
+    }
+
+    /// Rewinds the active branch to just before `id`, so the next message saved
+    /// to the conversation becomes a sibling of it. Returns the message.
+    pub fn branch_before(&self, id: i32) -> Result<Message> {
+        let message = self.get_message(id)?;
+        self.set_active_leaf(message.conversation_id, message.parent_id)?;
+        Ok(message)
+    }
+
+    /// Makes the branch through `id` active, continuing down its newest replies.
+    pub fn switch_branch(&self, id: i32) -> Result<()> {
+        let message = self.get_message(id)?;
+        let leaf = latest_leaf(&self.conn, message.conversation_id, Some(id))?;
+        self.set_active_leaf(message.conversation_id, leaf)
+    }
+
+    pub fn get_message(&self, id: i32) -> Result<Message> {
+        self.conn.query_row(
+            &format!("SELECT {} FROM messages WHERE id = ?1", MESSAGE_COLUMNS),
+            params![id],
+            message_from_row,
+        )
+    }
+
+    /// Runs an FTS5 `MATCH` expression (see `search::build_match_query`) and
+    /// returns hits best first.
+    pub fn search_messages(&self, match_query: &str, filters: &SearchFilters, limit: usize) -> Result<Vec<SearchHit>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {}, hits.hit_snippet, hits.hit_rank FROM messages
+             JOIN (
+                 SELECT rowid AS hit_id,
+                        snippet(messages_fts, 0, char(1), char(2), '…', 16) AS hit_snippet,
+                        bm25(messages_fts) AS hit_rank
+                 FROM messages_fts WHERE messages_fts MATCH ?1
+             ) hits ON hits.hit_id = messages.id
+             WHERE (?2 IS NULL OR role = ?2)
+               AND (?3 IS NULL OR timestamp >= ?3)
+               AND (?4 IS NULL OR timestamp <= ?4)
+               AND (?5 IS NULL OR model = ?5)
+               AND (?6 IS NULL OR EXISTS (SELECT 1 FROM attachments WHERE attachments.message_id = messages.id) = ?6)
+             ORDER BY hits.hit_rank ASC
+             LIMIT ?7",
+            MESSAGE_COLUMNS
+        ))?;
+
+        let rows = stmt.query_map(
+            params![match_query, filters.role, filters.from, filters.to, filters.model, filters.has_image, limit as i64],
+            |row| {
+                let snippet: String = row.get(MESSAGE_COLUMN_COUNT)?;
+                Ok((message_from_row(row)?, snippet, row.get::<_, f64>(MESSAGE_COLUMN_COUNT + 1)?))
+            },
+        )?;
+
+        let mut hits = Vec::new();
+        for row in rows {
+            let (message, snippet, rank) = row?;
+            let conversation = self.get_conversation(message.conversation_id)?;
+            hits.push(SearchHit {
+                message,
+                conversation,
+                snippet: crate::search::highlight_snippet(&snippet),
+                rank,
+            });
+        }
+        Ok(hits)
+    }
+
+    pub fn create_persona(
+        &self,
+        name: &str,
+        system_prompt: &str,
+        default_model: Option<&str>,
+        default_options: Option<&GenerationOptions>,
+        created_at: &str
+    ) -> Result<i32> {
+        self.conn.execute(
+            "INSERT INTO personas (name, system_prompt, default_model, default_options, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![name, system_prompt, default_model, default_options, created_at],
+        )?;
+        Ok(self.conn.last_insert_rowid() as i32)
+    }
+
+    pub fn update_persona(
+        &self,
+        id: i32,
+        name: &str,
+        system_prompt: &str,
+        default_model: Option<&str>,
+        default_options: Option<&GenerationOptions>
+    ) -> Result<()> {
+        let updated = self.conn.execute(
+            "UPDATE personas SET name = ?1, system_prompt = ?2, default_model = ?3, default_options = ?4 WHERE id = ?5",
+            params![name, system_prompt, default_model, default_options, id],
+        )?;
+        if updated == 0 {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+        Ok(())
+    }
+
+    pub fn delete_persona(&self, id: i32) -> Result<()> {
+        self.conn.execute("UPDATE conversations SET persona_id = NULL WHERE persona_id = ?1", params![id])?;
+        self.conn.execute("DELETE FROM personas WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    pub fn get_personas(&self) -> Result<Vec<Persona>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, conversation_id, role, content, input_type, image_path, image_filename, image_size, timestamp FROM messages 
-             WHERE conversation_id = ?1 ORDER BY timestamp ASC"
+            "SELECT id, name, system_prompt, default_model, default_options, created_at FROM personas ORDER BY name ASC"
         )?;
-        let message_iter = stmt.query_map(params![conversation_id], |row| {
-            Ok(Message {
+        let persona_iter = stmt.query_map([], |row| {
+            Ok(Persona {
                 id: row.get(0)?,
-                conversation_id: row.get(1)?,
-                role: row.get(2)?,
-                content: row.get(3)?,
-                input_type: row.get::<_, Option<String>>(4)?.unwrap_or_else(|| "text".to_string()),
-                image_path: row.get(5)?,
-                image_filename: row.get(6)?,
-                image_size: row.get(7)?,
-                timestamp: row.get(8)?,
+                name: row.get(1)?,
+                system_prompt: row.get(2)?,
+                default_model: row.get(3)?,
+                default_options: row.get(4)?,
+                created_at: row.get(5)?,
             })
         })?;
 
-        let mut messages = Vec::new();
-        for message in message_iter {
-            messages.push(message?);
+        let mut personas = Vec::new();
+        for persona in persona_iter {
+            personas.push(persona?);
+        }
+        Ok(personas)
+    }
+
+    pub fn get_conversation_persona(&self, conversation_id: i32) -> Result<Option<Persona>> {
+        let result = self.conn.query_row(
+            "SELECT p.id, p.name, p.system_prompt, p.default_model, p.default_options, p.created_at
+             FROM conversations c JOIN personas p ON p.id = c.persona_id
+             WHERE c.id = ?1",
+            params![conversation_id],
+            |row| {
+                Ok(Persona {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    system_prompt: row.get(2)?,
+                    default_model: row.get(3)?,
+                    default_options: row.get(4)?,
+                    created_at: row.get(5)?,
+                })
+            },
+        );
+        match result {
+            Ok(persona) => Ok(Some(persona)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn set_conversation_persona(&self, conversation_id: i32, persona_id: Option<i32>) -> Result<()> {
+        self.conn.execute(
+            "UPDATE conversations SET persona_id = ?1 WHERE id = ?2",
+            params![persona_id, conversation_id],
+        )?;
+        Ok(())
+    }
+
+    /// User and assistant messages with text that have no embedding from
+    /// `model` yet, oldest first.
+    pub fn get_unembedded_messages(&self, model: &str, limit: usize) -> Result<Vec<(i32, String)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT m.id, m.content FROM messages m
+             LEFT JOIN message_embeddings e ON e.message_id = m.id AND e.model = ?1
+             WHERE e.message_id IS NULL AND m.role IN ('user', 'assistant') AND TRIM(m.content) != ''
+             ORDER BY m.id ASC LIMIT ?2"
+        )?;
+        let rows = stmt.query_map(params![model, limit as i64], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    }
+
+    pub fn save_embedding(&self, message_id: i32, model: &str, vector: &[f32]) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO message_embeddings (message_id, model, dimensions, vector) VALUES (?1, ?2, ?3, ?4)",
+            params![message_id, model, vector.len() as i64, crate::embeddings::encode_vector(vector)],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_embeddings(&self, model: &str) -> Result<Vec<(i32, Vec<f32>)>> {
+        // Embeddings of messages lost before deletes cascaded are skipped
+        let mut stmt = self.conn.prepare(
+            "SELECT e.message_id, e.vector FROM message_embeddings e
+             JOIN messages m ON m.id = e.message_id
+             WHERE e.model = ?1"
+        )?;
+        let rows = stmt.query_map(params![model], |row| {
+            let vector: Vec<u8> = row.get(1)?;
+            Ok((row.get(0)?, crate::embeddings::decode_vector(&vector)))
+        })?;
+        rows.collect()
+    }
+
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>> {
+        match self.conn.query_row("SELECT value FROM settings WHERE key = ?1", params![key], |row| row.get(0)) {
+            Ok(value) => Ok(Some(value)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    pub fn add_endpoint(&self, endpoint: &Endpoint) -> Result<i32> {
+        self.conn.execute(
+            "INSERT INTO endpoints (name, base_url, provider, api_key, headers, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                endpoint.name,
+                endpoint.base_url,
+                endpoint.provider,
+                endpoint.api_key,
+                headers_to_sql(&endpoint.headers)?,
+                endpoint.created_at,
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid() as i32)
+    }
+
+    /// Updates everything but `created_at` of the endpoint with `endpoint.id`.
+    pub fn update_endpoint(&self, endpoint: &Endpoint) -> Result<()> {
+        let updated = self.conn.execute(
+            "UPDATE endpoints SET name = ?1, base_url = ?2, provider = ?3, api_key = ?4, headers = ?5 WHERE id = ?6",
+            params![
+                endpoint.name,
+                endpoint.base_url,
+                endpoint.provider,
+                endpoint.api_key,
+                headers_to_sql(&endpoint.headers)?,
+                endpoint.id,
+            ],
+        )?;
+        if updated == 0 {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+        Ok(())
+    }
+
+    pub fn get_endpoints(&self) -> Result<Vec<Endpoint>> {
+        let selected_id = self.get_selected_endpoint_id()?;
+        let mut stmt = self.conn.prepare(&format!("SELECT {} FROM endpoints ORDER BY id ASC", ENDPOINT_COLUMNS))?;
+        let endpoint_iter = stmt.query_map([], |row| endpoint_from_row(row, selected_id))?;
+
+        let mut endpoints = Vec::new();
+        for endpoint in endpoint_iter {
+            endpoints.push(endpoint?);
+        }
+        Ok(endpoints)
+    }
+
+    pub fn get_endpoint(&self, id: i32) -> Result<Endpoint> {
+        let selected_id = self.get_selected_endpoint_id()?;
+        self.conn.query_row(
+            &format!("SELECT {} FROM endpoints WHERE id = ?1", ENDPOINT_COLUMNS),
+            params![id],
+            |row| endpoint_from_row(row, selected_id),
+        )
+    }
+
+    pub fn select_endpoint(&self, id: i32) -> Result<()> {
+        self.set_setting(SELECTED_ENDPOINT_KEY, &id.to_string())
+    }
+
+    pub fn get_selected_endpoint_id(&self) -> Result<Option<i32>> {
+        Ok(self.get_setting(SELECTED_ENDPOINT_KEY)?.and_then(|value| value.parse().ok()))
+    }
+
+    /// The selected endpoint, or `None` if the selection points at nothing
+    /// and the local default should be used.
+    pub fn get_selected_endpoint(&self) -> Result<Option<Endpoint>> {
+        match self.get_selected_endpoint_id()? {
+            Some(id) => match self.get_endpoint(id) {
+                Ok(endpoint) => Ok(Some(endpoint)),
+                Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+                Err(e) => Err(e),
+            },
+            None => Ok(None),
         }
-        Ok(messages)
     }
 
     pub fn cleanup_orphaned_images(&self, images_dir: &std::path::Path) -> Result<()> {
         // Get all image paths from database
-        let mut stmt = self.conn.prepare("SELECT DISTINCT image_path FROM messages WHERE image_path IS NOT NULL")?;
+        let mut stmt = self.conn.prepare("SELECT DISTINCT path FROM attachments")?;
         let db_paths: std::collections::HashSet<String> = stmt.query_map([], |row| {
             Ok(row.get::<_, String>(0)?)
         })?.collect::<Result<_, _>>()?;
@@ -177,6 +1185,155 @@ impl Database {
     }
 }
 
+/// Follows the newest reply down from `from` (or from the newest root when
+/// `None`) to a leaf. `None` only when the conversation has no messages there.
+fn latest_leaf(conn: &Connection, conversation_id: i32, from: Option<i32>) -> Result<Option<i32>> {
+    let newest_child = |parent_id: Option<i32>| -> Result<Option<i32>> {
+        match conn.query_row(
+            "SELECT id FROM messages WHERE conversation_id = ?1 AND parent_id IS ?2 ORDER BY id DESC LIMIT 1",
+            params![conversation_id, parent_id],
+            |row| row.get(0),
+        ) {
+            Ok(id) => Ok(Some(id)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    };
+
+    let mut leaf = match from {
+        Some(id) => id,
+        None => match newest_child(None)? {
+            Some(id) => id,
+            None => return Ok(None),
+        },
+    };
+    while let Some(child) = newest_child(Some(leaf))? {
+        leaf = child;
+    }
+    Ok(Some(leaf))
+}
+
+const CONVERSATION_COLUMNS: &str = "id, created_at, endpoint_id, persona_id, title, updated_at, model, active_leaf_id, provider, context_policy";
+
+fn conversation_from_row(row: &rusqlite::Row) -> Result<Conversation> {
+    Ok(Conversation {
+        id: row.get(0)?,
+        created_at: row.get(1)?,
+        endpoint_id: row.get(2)?,
+        persona_id: row.get(3)?,
+        title: row.get(4)?,
+        updated_at: row.get(5)?,
+        model: row.get(6)?,
+        active_leaf_id: row.get(7)?,
+        provider: row.get(8)?,
+        context_policy: row.get(9)?,
+    })
+}
+
+const ENDPOINT_COLUMNS: &str = "id, name, base_url, provider, api_key, headers, created_at";
+
+fn endpoint_from_row(row: &rusqlite::Row, selected_id: Option<i32>) -> Result<Endpoint> {
+    let id: i32 = row.get(0)?;
+    let headers: Option<String> = row.get(5)?;
+    Ok(Endpoint {
+        id,
+        name: row.get(1)?,
+        base_url: row.get(2)?,
+        provider: row.get(3)?,
+        api_key: row.get(4)?,
+        headers: match headers {
+            Some(headers) => serde_json::from_str(&headers)
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(5, rusqlite::types::Type::Text, Box::new(e)))?,
+            None => BTreeMap::new(),
+        },
+        created_at: row.get(6)?,
+        is_selected: selected_id == Some(id),
+    })
+}
+
+fn headers_to_sql(headers: &BTreeMap<String, String>) -> Result<Option<String>> {
+    if headers.is_empty() {
+        return Ok(None);
+    }
+    serde_json::to_string(headers)
+        .map(Some)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+// The sibling counts refer to the outer table, so select these from `messages` without an alias
+const MESSAGE_COLUMNS: &str = "id, conversation_id, role, content, input_type, timestamp, status, tool_calls, tool_name, structured_content, model, parent_id, \
+    (SELECT COUNT(*) FROM messages AS siblings WHERE siblings.conversation_id = messages.conversation_id \
+        AND siblings.parent_id IS messages.parent_id AND siblings.id <= messages.id), \
+    (SELECT COUNT(*) FROM messages AS siblings WHERE siblings.conversation_id = messages.conversation_id \
+        AND siblings.parent_id IS messages.parent_id), \
+    total_duration, load_duration, prompt_eval_count, eval_count, eval_duration, model_digest, \
+    (SELECT json_group_array(json_object('path', path, 'filename', filename, 'size', size)) FROM \
+        (SELECT path, filename, size FROM attachments WHERE attachments.message_id = messages.id ORDER BY position)), \
+    reasoning";
+const MESSAGE_COLUMN_COUNT: usize = 22;
+
+fn message_from_row(row: &rusqlite::Row) -> Result<Message> {
+    Ok(Message {
+        id: row.get(0)?,
+        conversation_id: row.get(1)?,
+        role: row.get(2)?,
+        content: row.get(3)?,
+        reasoning: row.get(21)?,
+        input_type: row.get::<_, Option<String>>(4)?.unwrap_or_else(|| "text".to_string()),
+        attachments: serde_json::from_str(&row.get::<_, String>(20)?)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(20, rusqlite::types::Type::Text, Box::new(e)))?,
+        timestamp: row.get(5)?,
+        status: row.get::<_, Option<String>>(6)?.unwrap_or_else(|| MESSAGE_STATUS_COMPLETE.to_string()),
+        tool_calls: row.get::<_, Option<String>>(7)?.and_then(|json| serde_json::from_str(&json).ok()),
+        tool_name: row.get(8)?,
+        structured_content: row.get::<_, Option<String>>(9)?.and_then(|json| serde_json::from_str(&json).ok()),
+        model: row.get(10)?,
+        metrics: Some(ReplyMetrics {
+            total_duration: row.get(14)?,
+            load_duration: row.get(15)?,
+            prompt_eval_count: row.get(16)?,
+            eval_count: row.get(17)?,
+            eval_duration: row.get(18)?,
+            model_digest: row.get(19)?,
+        }).filter(|metrics| !metrics.is_empty()),
+        parent_id: row.get(11)?,
+        sibling_index: row.get(12)?,
+        sibling_count: row.get(13)?,
+    })
+}
+
+/// Opens `path` with `passphrase` as the SQLCipher key, or returns `None`
+/// when the file is encrypted and the passphrase is missing or wrong.
+fn open_connection(path: &Path, passphrase: Option<&str>) -> Result<Option<Connection>> {
+    let conn = Connection::open(path)?;
+    if let Some(passphrase) = passphrase {
+        conn.pragma_update(None, "key", passphrase)?;
+    }
+    match conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |_| Ok(())) {
+        Ok(()) => Ok(Some(conn)),
+        Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == rusqlite::ErrorCode::NotADatabase => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn load_image_key(conn: &Connection) -> Result<Option<ImageKey>> {
+    let value: Option<String> = match conn.query_row(
+        "SELECT value FROM settings WHERE key = ?1",
+        params![IMAGE_KEY_SETTING],
+        |row| row.get(0),
+    ) {
+        Ok(value) => Some(value),
+        Err(rusqlite::Error::QueryReturnedNoRows) => None,
+        Err(e) => return Err(e),
+    };
+    value.map(|value| {
+        general_purpose::STANDARD.decode(value)
+            .ok()
+            .and_then(|bytes| ImageKey::try_from(bytes.as_slice()).ok())
+            .ok_or_else(|| migrations::failure("Stored image key is invalid.".to_string()))
+    }).transpose()
+}
+
 fn get_db_path() -> PathBuf {
     let mut path = dirs::data_local_dir()
         .or_else(|| dirs::home_dir())
@@ -0,0 +1,95 @@
+use crate::db::{Conversation, Message};
+use serde::Serialize;
+
+pub const DEFAULT_EMBEDDING_MODEL: &str = "nomic-embed-text";
+pub const EMBEDDING_MODEL_KEY: &str = "embedding_model";
+
+// Messages embedded per request while catching up on history
+pub const EMBEDDING_BATCH_SIZE: usize = 32;
+
+/// App event emitted when the background embedding worker's status changes.
+pub const EMBEDDING_STATUS_EVENT: &str = "embedding-status";
+
+/// What the background embedding worker is doing, for the frontend.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum EmbeddingStatus {
+    /// Caught up, or catching up without errors.
+    Ready,
+    /// A batch failed and is tried again after `retry_in_secs`.
+    Retrying { error: String, retry_in_secs: u64 },
+    /// The server does not have `model`. It is tried again once the setting
+    /// changes, a model is pulled, another endpoint is selected, or an hour
+    /// has passed.
+    UnknownModel { model: String, error: String },
+}
+
+/// Why the worker could not embed a batch.
+pub enum EmbeddingFailure {
+    UnknownModel { model: String, error: String },
+    Failed(String),
+}
+
+impl From<String> for EmbeddingFailure {
+    fn from(error: String) -> Self {
+        EmbeddingFailure::Failed(error)
+    }
+}
+
+/// Whether an embedding request failed because the server lacks the model,
+/// e.g. Ollama's `model "x" not found, try pulling it first`. Retrying
+/// cannot fix that.
+pub fn is_unknown_model_error(error: &str) -> bool {
+    let error = error.to_lowercase();
+    error.contains("model") && (error.contains("not found") || error.contains("does not exist"))
+}
+
+#[derive(Debug, Serialize)]
+pub struct SemanticMatch {
+    pub score: f32,
+    pub message: Message,
+    pub conversation: Conversation,
+}
+
+/// Vectors are stored as little-endian `f32` blobs.
+pub fn encode_vector(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+pub fn decode_vector(bytes: &[u8]) -> Vec<f32> {
+    bytes.chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+
+    let mut dot = 0.0;
+    let mut norm_a = 0.0;
+    let mut norm_b = 0.0;
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a.sqrt() * norm_b.sqrt())
+    }
+}
+
+/// Scores every candidate against `query` and keeps the `top_k` best as
+/// `(message_id, score)` pairs, highest first.
+pub fn rank(query: &[f32], candidates: Vec<(i32, Vec<f32>)>, top_k: usize) -> Vec<(i32, f32)> {
+    let mut scored: Vec<(i32, f32)> = candidates.into_iter()
+        .map(|(message_id, vector)| (message_id, cosine_similarity(query, &vector)))
+        .collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored.truncate(top_k);
+    scored
+}
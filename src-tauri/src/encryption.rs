@@ -0,0 +1,161 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// Image files are encrypted with a random key kept in the (encrypted)
+/// settings table, so changing the passphrase never touches them.
+pub const IMAGE_KEY_SETTING: &str = "image_encryption_key";
+
+// Marks an encrypted image so plain and encrypted files can coexist while
+// an existing image folder is being converted
+const IMAGE_MAGIC: &[u8] = b"CHATENC1";
+const NONCE_LEN: usize = 12;
+
+pub type ImageKey = [u8; 32];
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct EncryptionStatus {
+    pub encrypted: bool,
+    /// The database is encrypted and no passphrase has been entered yet.
+    pub locked: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EncryptionReport {
+    pub images_encrypted: usize,
+    /// Plain database and image copies still in the data folder, for the
+    /// user to confirm before they are removed.
+    pub plain_copies: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RemovalReport {
+    pub removed: Vec<String>,
+    pub failed: Vec<RemovalFailure>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RemovalFailure {
+    pub path: String,
+    pub error: String,
+}
+
+pub fn generate_image_key() -> ImageKey {
+    Aes256Gcm::generate_key(&mut OsRng).into()
+}
+
+pub fn encrypt_image(key: &ImageKey, bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher.encrypt(&nonce, bytes).map_err(|_| "Failed to encrypt image".to_string())?;
+
+    let mut encrypted = Vec::with_capacity(IMAGE_MAGIC.len() + NONCE_LEN + ciphertext.len());
+    encrypted.extend_from_slice(IMAGE_MAGIC);
+    encrypted.extend_from_slice(&nonce);
+    encrypted.extend_from_slice(&ciphertext);
+    Ok(encrypted)
+}
+
+/// Returns the plain image, decrypting it if it was written encrypted.
+pub fn decrypt_image(key: Option<&ImageKey>, data: Vec<u8>) -> Result<Vec<u8>, String> {
+    let Some(payload) = data.strip_prefix(IMAGE_MAGIC) else {
+        return Ok(data);
+    };
+    let key = key.ok_or("Image is encrypted; unlock the database first.")?;
+    if payload.len() < NONCE_LEN {
+        return Err("Encrypted image is truncated".to_string());
+    }
+    let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Failed to decrypt image".to_string())
+}
+
+pub fn read_image(path: &Path, key: Option<&ImageKey>) -> Result<Vec<u8>, String> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read image file: {}", e))?;
+    decrypt_image(key, data)
+}
+
+/// Writes an image, encrypted when a key is given.
+pub fn write_image(path: &Path, bytes: &[u8], key: Option<&ImageKey>) -> Result<(), String> {
+    let result = match key {
+        Some(key) => fs::write(path, encrypt_image(key, bytes)?),
+        None => fs::write(path, bytes),
+    };
+    result.map_err(|e| format!("Failed to write image file: {}", e))
+}
+
+/// Encrypts every image under `images_dir` that is still plain, returning
+/// how many were converted. Each file is replaced atomically, so an
+/// interrupted run can simply be repeated.
+pub fn encrypt_images_in_dir(images_dir: &Path, key: &ImageKey) -> Result<usize, String> {
+    let mut converted = 0;
+    let mut pending = vec![images_dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let data = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            if data.starts_with(IMAGE_MAGIC) {
+                continue;
+            }
+
+            let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+            temp_name.push(".encrypting");
+            let temp_path = path.with_file_name(temp_name);
+            fs::write(&temp_path, encrypt_image(key, &data)?)
+                .and_then(|_| fs::rename(&temp_path, &path))
+                .map_err(|e| format!("Failed to encrypt {}: {}", path.display(), e))?;
+            converted += 1;
+        }
+    }
+    Ok(converted)
+}
+
+/// The safety copies left in `data_dir` that encryption does not cover:
+/// pre-migration and pre-restore database backups and pre-restore image
+/// folders.
+pub fn plain_copies(data_dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(data_dir) else {
+        return Vec::new();
+    };
+    let mut copies: Vec<String> = entries.flatten()
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            (name.starts_with("chat.") && name.ends_with(".bak"))
+                || (name.starts_with("images.pre-restore-") && entry.path().is_dir())
+        })
+        .map(|entry| entry.path().to_string_lossy().to_string())
+        .collect();
+    copies.sort();
+    copies
+}
+
+/// Deletes the confirmed `paths` among the plain copies in `data_dir`.
+/// Anything else is refused rather than deleted.
+pub fn remove_plain_copies(data_dir: &Path, paths: &[String]) -> RemovalReport {
+    let copies = plain_copies(data_dir);
+    let mut report = RemovalReport { removed: Vec::new(), failed: Vec::new() };
+    for path in paths {
+        let result = if !copies.contains(path) {
+            Err("Not an unencrypted copy in the data folder".to_string())
+        } else if Path::new(path).is_dir() {
+            fs::remove_dir_all(path).map_err(|e| e.to_string())
+        } else {
+            fs::remove_file(path).map_err(|e| e.to_string())
+        };
+        match result {
+            Ok(()) => report.removed.push(path.clone()),
+            Err(error) => report.failed.push(RemovalFailure { path: path.clone(), error }),
+        }
+    }
+    report
+}
@@ -0,0 +1,279 @@
+use crate::db::{Conversation, Message, MESSAGE_STATUS_CANCELLED};
+use crate::encryption::{self, ImageKey};
+use base64::{Engine as _, engine::general_purpose};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Identifies our JSON exports so the importer can recognise them.
+pub const EXPORT_FORMAT_ID: &str = "tauri-chat-app.export";
+pub const EXPORT_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+    Html,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Json => "json",
+            ExportFormat::Html => "html",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportDocument {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: String,
+    pub conversations: Vec<ConversationExport>,
+}
+
+/// A conversation with its messages: every branch for JSON, the active
+/// branch for Markdown and HTML.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConversationExport {
+    pub conversation: Conversation,
+    pub messages: Vec<ExportedMessage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedMessage {
+    #[serde(flatten)]
+    pub message: Message,
+    /// Base64 contents of each attachment in order, so the export does not
+    /// depend on `images/`. `None` where the file has gone missing.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachment_data: Vec<Option<String>>,
+    /// Version 1 exports carried at most one image, on the message itself.
+    #[serde(default, skip_serializing)]
+    pub image_path: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub image_filename: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub image_data: Option<String>,
+}
+
+impl ExportedMessage {
+    pub fn new(message: Message) -> Self {
+        ExportedMessage {
+            message,
+            attachment_data: Vec::new(),
+            image_path: None,
+            image_filename: None,
+            image_data: None,
+        }
+    }
+}
+
+/// Reads the attachments of every message. Files that have gone missing
+/// are left out rather than failing the export.
+pub fn load_images(exports: &mut [ConversationExport], image_key: Option<&ImageKey>) {
+    for exported in exports.iter_mut().flat_map(|export| export.messages.iter_mut()) {
+        exported.attachment_data = exported.message.attachments.iter()
+            .map(|attachment| {
+                encryption::read_image(Path::new(&attachment.path), image_key)
+                    .ok()
+                    .map(|bytes| general_purpose::STANDARD.encode(bytes))
+            })
+            .collect();
+    }
+}
+
+/// Renders the conversations in `format`. Markdown links images by path
+/// unless `embed_images` is set and they were loaded; HTML always inlines them.
+pub fn render(exports: Vec<ConversationExport>, format: ExportFormat, embed_images: bool, exported_at: &str) -> Result<String, String> {
+    match format {
+        ExportFormat::Json => {
+            let document = ExportDocument {
+                format: EXPORT_FORMAT_ID.to_string(),
+                version: EXPORT_VERSION,
+                exported_at: exported_at.to_string(),
+                conversations: exports,
+            };
+            serde_json::to_string_pretty(&document).map_err(|e| format!("Failed to serialize export: {}", e))
+        }
+        ExportFormat::Markdown => Ok(to_markdown(&exports, embed_images)),
+        ExportFormat::Html => Ok(to_html(&exports, exported_at)),
+    }
+}
+
+fn to_markdown(exports: &[ConversationExport], embed_images: bool) -> String {
+    let mut markdown = String::new();
+    for (index, export) in exports.iter().enumerate() {
+        if index > 0 {
+            markdown.push_str("\n---\n\n");
+        }
+        let conversation = &export.conversation;
+        markdown.push_str(&format!("# {}\n\n", conversation_title(conversation)));
+        markdown.push_str(&format!("_Created {}", conversation.created_at));
+        if let Some(model) = &conversation.model {
+            markdown.push_str(&format!(" · {}", model));
+        }
+        markdown.push_str("_\n\n");
+
+        for exported in &export.messages {
+            let message = &exported.message;
+            markdown.push_str(&format!("### {} · {}\n\n", speaker(message), message.timestamp));
+
+            for (index, attachment) in message.attachments.iter().enumerate() {
+                let alt = attachment.filename.as_deref().unwrap_or("image");
+                match (exported.attachment_data.get(index).and_then(Option::as_ref), embed_images) {
+                    (Some(data), true) => markdown.push_str(&format!(
+                        "![{}](data:{};base64,{})\n\n",
+                        alt, image_mime_type(&attachment.path), data
+                    )),
+                    _ => markdown.push_str(&format!("![{}](<{}>)\n\n", alt, attachment.path)),
+                }
+            }
+
+            if let Some(reasoning) = &message.reasoning {
+                markdown.push_str(&format!("<details>\n<summary>Thinking</summary>\n\n{}\n\n</details>\n\n", reasoning));
+            }
+            if message.role == "tool" {
+                markdown.push_str(&format!("```\n{}\n```\n\n", message.content));
+            } else if !message.content.is_empty() {
+                markdown.push_str(&message.content);
+                markdown.push_str("\n\n");
+            }
+            if let Some(tool_calls) = &message.tool_calls {
+                let calls = serde_json::to_string_pretty(tool_calls).unwrap_or_default();
+                markdown.push_str(&format!("Tool calls:\n\n```json\n{}\n```\n\n", calls));
+            }
+            if message.status == MESSAGE_STATUS_CANCELLED {
+                markdown.push_str("_Generation stopped_\n\n");
+            }
+        }
+    }
+    markdown
+}
+
+fn to_html(exports: &[ConversationExport], exported_at: &str) -> String {
+    let title = match exports {
+        [export] => conversation_title(&export.conversation),
+        _ => "Conversations".to_string(),
+    };
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n", escape_html(&title), HTML_STYLE));
+
+    for export in exports {
+        let conversation = &export.conversation;
+        html.push_str("<section class=\"conversation\">\n");
+        html.push_str(&format!("<h1>{}</h1>\n", escape_html(&conversation_title(conversation))));
+        html.push_str(&format!("<p class=\"meta\">Created {}", escape_html(&conversation.created_at)));
+        if let Some(model) = &conversation.model {
+            html.push_str(&format!(" · {}", escape_html(model)));
+        }
+        html.push_str("</p>\n");
+
+        for exported in &export.messages {
+            let message = &exported.message;
+            html.push_str(&format!("<div class=\"message {}\">\n", escape_html(&message.role)));
+            html.push_str(&format!(
+                "<div class=\"speaker\">{} <span class=\"time\">{}</span></div>\n",
+                escape_html(&speaker(message)),
+                escape_html(&message.timestamp)
+            ));
+
+            for (attachment, data) in message.attachments.iter().zip(&exported.attachment_data) {
+                let Some(data) = data else {
+                    continue;
+                };
+                html.push_str(&format!(
+                    "<img src=\"data:{};base64,{}\" alt=\"{}\">\n",
+                    image_mime_type(&attachment.path),
+                    data,
+                    escape_html(attachment.filename.as_deref().unwrap_or("image"))
+                ));
+            }
+            if let Some(reasoning) = &message.reasoning {
+                html.push_str(&format!(
+                    "<details class=\"reasoning\"><summary>Thinking</summary><div class=\"content\">{}</div></details>\n",
+                    escape_html(reasoning)
+                ));
+            }
+            if !message.content.is_empty() {
+                html.push_str(&format!("<div class=\"content\">{}</div>\n", escape_html(&message.content)));
+            }
+            if let Some(tool_calls) = &message.tool_calls {
+                let calls = serde_json::to_string_pretty(tool_calls).unwrap_or_default();
+                html.push_str(&format!("<pre class=\"tool-calls\">{}</pre>\n", escape_html(&calls)));
+            }
+            if message.status == MESSAGE_STATUS_CANCELLED {
+                html.push_str("<p class=\"meta\">Generation stopped</p>\n");
+            }
+            html.push_str("</div>\n");
+        }
+        html.push_str("</section>\n");
+    }
+
+    html.push_str(&format!("<footer class=\"meta\">Exported {}</footer>\n</body>\n</html>\n", escape_html(exported_at)));
+    html
+}
+
+const HTML_STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:48rem;margin:2rem auto;padding:0 1rem;color:#111827}\
+    .conversation{margin-bottom:3rem}.meta{color:#6b7280;font-size:.85rem}\
+    .message{margin:1rem 0;padding:.75rem 1rem;border-radius:1rem;background:#f3f4f6;border:1px solid #e5e7eb}\
+    .message.user{background:#3b82f6;color:#fff;border-color:#3b82f6;margin-left:3rem}\
+    .message.tool,.message.system{background:#fffbeb;border-color:#fde68a}\
+    .speaker{font-weight:600;font-size:.8rem;margin-bottom:.25rem}.time{font-weight:400;opacity:.7}\
+    .content{white-space:pre-wrap;line-height:1.5}img{max-width:100%;max-height:300px;border-radius:.5rem}\
+    pre{white-space:pre-wrap;font-size:.8rem}\
+    .reasoning{margin-bottom:.5rem;font-size:.85rem;opacity:.8}.reasoning summary{cursor:pointer}";
+
+fn conversation_title(conversation: &Conversation) -> String {
+    conversation.title.clone().unwrap_or_else(|| format!("Conversation #{}", conversation.id))
+}
+
+fn speaker(message: &Message) -> String {
+    match message.role.as_str() {
+        "user" => "User".to_string(),
+        "assistant" => match &message.model {
+            Some(model) => format!("Assistant ({})", model),
+            None => "Assistant".to_string(),
+        },
+        "tool" => match &message.tool_name {
+            Some(name) => format!("Tool ({})", name),
+            None => "Tool".to_string(),
+        },
+        "system" => "System".to_string(),
+        role => role.to_string(),
+    }
+}
+
+fn image_mime_type(image_path: &str) -> &'static str {
+    let extension = Path::new(image_path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("bmp") => "image/bmp",
+        Some("svg") => "image/svg+xml",
+        _ => "image/jpeg",
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,36 @@
+use crate::db::Message;
+use crate::encryption::{self, ImageKey};
+use crate::ollama::ChatMessage;
+use base64::{Engine as _, engine::general_purpose};
+use std::path::Path;
+
+/// Setting holding how many of the most recent images are resent with the history.
+pub const HISTORY_IMAGE_LIMIT_KEY: &str = "history_image_limit";
+pub const DEFAULT_HISTORY_IMAGE_LIMIT: usize = 4;
+
+/// Turns stored messages into chat history. The newest `image_limit` images
+/// go back to the model with their messages, so follow-up questions about
+/// an image keep it in view; older messages are sent as text only. Images
+/// whose files have gone missing are skipped.
+pub fn build(messages: &[Message], image_limit: usize, image_key: Option<&ImageKey>) -> Vec<ChatMessage> {
+    let mut remaining = image_limit;
+    let mut history: Vec<ChatMessage> = messages.iter().rev()
+        .map(|message| {
+            let mut chat_message = message.to_chat_message();
+            let take = remaining.min(message.attachments.len());
+            remaining -= take;
+
+            // Within a message, the last images attached are the ones kept
+            let images: Vec<String> = message.attachments[message.attachments.len() - take..].iter()
+                .filter_map(|attachment| encryption::read_image(Path::new(&attachment.path), image_key).ok())
+                .map(|bytes| general_purpose::STANDARD.encode(bytes))
+                .collect();
+            if !images.is_empty() {
+                chat_message.images = Some(images);
+            }
+            chat_message
+        })
+        .collect();
+    history.reverse();
+    history
+}
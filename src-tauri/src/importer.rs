@@ -0,0 +1,567 @@
+use crate::db::{Attachment, Database, MESSAGE_STATUS_COMPLETE};
+use crate::encryption::{self, ImageKey};
+use crate::ollama::ReplyMetrics;
+use crate::export::{ExportDocument, EXPORT_FORMAT_ID};
+use crate::reasoning;
+use base64::{Engine as _, engine::general_purpose};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// What an import did, for showing to the user.
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub source: String,
+    pub conversations_imported: usize,
+    pub messages_imported: usize,
+    pub images_imported: usize,
+    pub duplicates_skipped: usize,
+    pub empty_skipped: usize,
+    /// One line per conversation that could not be imported.
+    pub failures: Vec<String>,
+}
+
+/// A conversation parsed from any supported format, ready to be saved.
+struct ImportedConversation {
+    title: Option<String>,
+    created_at: String,
+    /// Parents always come before their replies.
+    messages: Vec<ImportedMessage>,
+    /// Index of the message whose branch should be shown.
+    active_leaf: Option<usize>,
+}
+
+struct ImportedMessage {
+    parent: Option<usize>,
+    role: String,
+    content: String,
+    reasoning: Option<String>,
+    timestamp: String,
+    status: String,
+    model: Option<String>,
+    tool_calls: Option<Value>,
+    tool_name: Option<String>,
+    structured_content: Option<Value>,
+    metrics: Option<ReplyMetrics>,
+    images: Vec<ImportedImage>,
+}
+
+struct ImportedImage {
+    filename: String,
+    extension: String,
+    bytes: Vec<u8>,
+}
+
+impl ImportedMessage {
+    /// A plain message; assistant replies with inline `<think>` blocks get
+    /// their reasoning moved out of `content`.
+    fn text(role: &str, content: String, timestamp: String) -> Self {
+        let (content, reasoning) = match role {
+            "assistant" => reasoning::split(&content),
+            _ => (content, None),
+        };
+        ImportedMessage {
+            parent: None,
+            role: role.to_string(),
+            content,
+            reasoning,
+            timestamp,
+            status: MESSAGE_STATUS_COMPLETE.to_string(),
+            model: None,
+            tool_calls: None,
+            tool_name: None,
+            structured_content: None,
+            metrics: None,
+            images: Vec::new(),
+        }
+    }
+}
+
+/// Detects the format of `contents` (ChatGPT `conversations.json`, an Open
+/// WebUI export or our own JSON export) and saves every conversation that is
+/// not already in the database, keeping the original timestamps. Images are
+/// written under `images_dir/<conversation_id>/`.
+pub fn import(db: &Database, contents: &str, images_dir: &Path) -> Result<ImportReport, String> {
+    let value: Value = serde_json::from_str(contents).map_err(|e| format!("Import file is not valid JSON: {}", e))?;
+    let (source, conversations) = parse(value)?;
+
+    let mut report = ImportReport {
+        source: source.to_string(),
+        ..Default::default()
+    };
+    for conversation in conversations {
+        let title = conversation.title.clone().unwrap_or_else(|| "Untitled".to_string());
+        if let Err(e) = save_conversation(db, conversation, images_dir, &mut report) {
+            report.failures.push(format!("{}: {}", title, e));
+        }
+    }
+    Ok(report)
+}
+
+fn parse(value: Value) -> Result<(&'static str, Vec<ImportedConversation>), String> {
+    if value.get("format").and_then(Value::as_str) == Some(EXPORT_FORMAT_ID) {
+        let document: ExportDocument = serde_json::from_value(value)
+            .map_err(|e| format!("Invalid export file: {}", e))?;
+        return Ok(("this app", parse_own_export(document)));
+    }
+
+    let items = match value {
+        Value::Array(items) => items,
+        single @ Value::Object(_) => vec![single],
+        _ => return Err("Unrecognised import file.".to_string()),
+    };
+    let Some(first) = items.first() else {
+        return Ok(("empty file", Vec::new()));
+    };
+
+    if first.get("mapping").is_some() {
+        let conversations: Vec<ChatGptConversation> = serde_json::from_value(Value::Array(items))
+            .map_err(|e| format!("Invalid ChatGPT export: {}", e))?;
+        Ok(("ChatGPT", conversations.into_iter().map(parse_chatgpt).collect()))
+    } else if first.get("chat").is_some() {
+        let chats: Vec<OpenWebUiChat> = serde_json::from_value(Value::Array(items))
+            .map_err(|e| format!("Invalid Open WebUI export: {}", e))?;
+        Ok(("Open WebUI", chats.into_iter().map(parse_open_webui).collect()))
+    } else {
+        Err("Unrecognised import file; expected a ChatGPT, Open WebUI or chat app export.".to_string())
+    }
+}
+
+fn save_conversation(
+    db: &Database,
+    conversation: ImportedConversation,
+    images_dir: &Path,
+    report: &mut ImportReport,
+) -> Result<(), String> {
+    let Some(first) = conversation.messages.first() else {
+        report.empty_skipped += 1;
+        return Ok(());
+    };
+    let duplicate = db.find_matching_conversation(&conversation.created_at, &first.role, &first.content, &first.timestamp)
+        .map_err(|e| format!("Database error: {}", e))?;
+    if duplicate.is_some() {
+        report.duplicates_skipped += 1;
+        return Ok(());
+    }
+
+    // Image files are outside the transaction, so they are removed by hand
+    // if it rolls back; the conversation id, and with it the folder, is reused
+    let mut written: Vec<String> = Vec::new();
+    let saved = db.in_transaction(|db| {
+        let conversation_id = db.create_conversation(&conversation.created_at)?;
+        db.rename_conversation(conversation_id, conversation.title.as_deref())?;
+
+        let mut ids: Vec<i32> = Vec::with_capacity(conversation.messages.len());
+        let mut images = 0;
+        for (index, message) in conversation.messages.iter().enumerate() {
+            db.set_active_leaf(conversation_id, message.parent.map(|parent| ids[parent]))?;
+
+            let attachments = message.images.iter().enumerate()
+                .map(|(position, image)| {
+                    let path = write_image(images_dir, conversation_id, index, position, image, db.image_key())?;
+                    written.push(path.clone());
+                    Ok(Attachment {
+                        path,
+                        filename: Some(image.filename.clone()),
+                        size: Some(image.bytes.len() as i32),
+                    })
+                })
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            let input_type = match (attachments.is_empty(), message.content.is_empty()) {
+                (true, _) => "text",
+                (false, true) => "image",
+                (false, false) => "mixed",
+            };
+            let id = db.save_message_with_attachments(
+                conversation_id,
+                &message.role,
+                &message.content,
+                input_type,
+                &attachments,
+                &message.timestamp,
+            )?;
+            db.restore_message_fields(
+                id,
+                &message.status,
+                message.model.as_deref(),
+                message.tool_calls.as_ref().map(Value::to_string).as_deref(),
+                message.tool_name.as_deref(),
+                message.structured_content.as_ref().map(Value::to_string).as_deref(),
+            )?;
+            db.set_message_reasoning(id, message.reasoning.as_deref())?;
+            db.set_message_metrics(id, message.metrics.as_ref())?;
+            ids.push(id);
+            images += attachments.len();
+        }
+
+        let active_leaf = conversation.active_leaf.map(|index| ids[index]).or(ids.last().copied());
+        db.set_active_leaf(conversation_id, active_leaf)?;
+        Ok(images)
+    });
+    let images = match saved {
+        Ok(images) => images,
+        Err(e) => {
+            remove_written_images(&written);
+            return Err(format!("Database error: {}", e));
+        }
+    };
+
+    report.conversations_imported += 1;
+    report.messages_imported += conversation.messages.len();
+    report.images_imported += images;
+    Ok(())
+}
+
+fn remove_written_images(paths: &[String]) {
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+    // Only succeeds once a folder is empty, so other images are kept
+    for path in paths {
+        if let Some(dir) = Path::new(path).parent() {
+            let _ = fs::remove_dir(dir);
+        }
+    }
+}
+
+fn write_image(
+    images_dir: &Path,
+    conversation_id: i32,
+    index: usize,
+    position: usize,
+    image: &ImportedImage,
+    image_key: Option<&ImageKey>,
+) -> rusqlite::Result<String> {
+    let conv_dir = images_dir.join(conversation_id.to_string());
+    let path = conv_dir.join(format!("imported_{}_{}_{}.{}", conversation_id, index, position, image.extension));
+    fs::create_dir_all(&conv_dir)
+        .map_err(|e| format!("Failed to write image file: {}", e))
+        .and_then(|_| encryption::write_image(&path, &image.bytes, image_key))
+        .map_err(|e| {
+            rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CANTOPEN),
+                Some(e),
+            )
+        })?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+// Our own export: message ids and parents are from the exporting database
+
+fn parse_own_export(document: ExportDocument) -> Vec<ImportedConversation> {
+    document.conversations.into_iter()
+        .map(|export| {
+            let mut indices: HashMap<i32, usize> = HashMap::new();
+            let mut messages = Vec::with_capacity(export.messages.len());
+            for exported in export.messages {
+                let message = exported.message;
+                let images = match (exported.image_path, exported.image_data) {
+                    // Version 1: one image on the message
+                    (Some(image_path), Some(data)) => {
+                        exported_image(&image_path, exported.image_filename.as_deref(), &data).into_iter().collect()
+                    }
+                    _ => message.attachments.iter().zip(&exported.attachment_data)
+                        .filter_map(|(attachment, data)| {
+                            exported_image(&attachment.path, attachment.filename.as_deref(), data.as_deref()?)
+                        })
+                        .collect(),
+                };
+                indices.insert(message.id, messages.len());
+                messages.push(ImportedMessage {
+                    parent: message.parent_id.and_then(|parent_id| indices.get(&parent_id).copied()),
+                    role: message.role,
+                    content: message.content,
+                    reasoning: message.reasoning,
+                    timestamp: message.timestamp,
+                    status: message.status,
+                    model: message.model,
+                    tool_calls: message.tool_calls,
+                    tool_name: message.tool_name,
+                    structured_content: message.structured_content,
+                    metrics: message.metrics,
+                    images,
+                });
+            }
+
+            ImportedConversation {
+                active_leaf: export.conversation.active_leaf_id.and_then(|id| indices.get(&id).copied()),
+                title: export.conversation.title,
+                created_at: export.conversation.created_at,
+                messages,
+            }
+        })
+        .collect()
+}
+
+fn exported_image(path: &str, filename: Option<&str>, data: &str) -> Option<ImportedImage> {
+    Some(ImportedImage {
+        filename: filename.unwrap_or("image").to_string(),
+        extension: Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("jpg")
+            .to_string(),
+        bytes: general_purpose::STANDARD.decode(data).ok()?,
+    })
+}
+
+// ChatGPT `conversations.json`: each conversation is a tree of nodes keyed by id
+
+#[derive(Deserialize)]
+struct ChatGptConversation {
+    title: Option<String>,
+    create_time: Option<f64>,
+    #[serde(default)]
+    mapping: HashMap<String, ChatGptNode>,
+    current_node: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatGptNode {
+    message: Option<ChatGptMessage>,
+    parent: Option<String>,
+    #[serde(default)]
+    children: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatGptMessage {
+    author: ChatGptAuthor,
+    create_time: Option<f64>,
+    content: Option<ChatGptContent>,
+    #[serde(default)]
+    metadata: Value,
+}
+
+#[derive(Deserialize)]
+struct ChatGptAuthor {
+    role: String,
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatGptContent {
+    #[serde(default)]
+    parts: Vec<Value>,
+    text: Option<String>,
+}
+
+fn parse_chatgpt(conversation: ChatGptConversation) -> ImportedConversation {
+    let created_at = conversation.create_time.and_then(epoch_to_timestamp).unwrap_or_else(now_timestamp);
+
+    let nodes = conversation.mapping.into_iter()
+        .map(|(id, node)| {
+            let message = node.message.and_then(|message| {
+                // Text parts only; uploaded files are not part of conversations.json
+                let content = message.content.map(|content| {
+                    let parts: Vec<&str> = content.parts.iter().filter_map(Value::as_str).collect();
+                    if parts.is_empty() { content.text.unwrap_or_default() } else { parts.join("\n") }
+                }).unwrap_or_default();
+                if content.trim().is_empty() || !matches!(message.author.role.as_str(), "user" | "assistant" | "tool") {
+                    return None;
+                }
+
+                let timestamp = message.create_time.and_then(epoch_to_timestamp).unwrap_or_else(|| created_at.clone());
+                let mut imported = ImportedMessage::text(&message.author.role, content, timestamp);
+                imported.model = message.metadata.get("model_slug").and_then(Value::as_str).map(str::to_string);
+                if message.author.role == "tool" {
+                    imported.tool_name = message.author.name;
+                }
+                Some(imported)
+            });
+            (id, TreeNode { parent: node.parent, children: node.children, message })
+        })
+        .collect();
+
+    let (messages, active_leaf) = flatten_tree(nodes, conversation.current_node.as_deref());
+    ImportedConversation {
+        title: conversation.title,
+        created_at,
+        messages,
+        active_leaf,
+    }
+}
+
+// Open WebUI: chats hold a message tree under `history`, or a flat list in older exports
+
+#[derive(Deserialize)]
+struct OpenWebUiChat {
+    title: Option<String>,
+    created_at: Option<f64>,
+    chat: OpenWebUiChatBody,
+}
+
+#[derive(Deserialize)]
+struct OpenWebUiChatBody {
+    title: Option<String>,
+    timestamp: Option<f64>,
+    history: Option<OpenWebUiHistory>,
+    #[serde(default)]
+    messages: Vec<OpenWebUiMessage>,
+}
+
+#[derive(Deserialize)]
+struct OpenWebUiHistory {
+    #[serde(default)]
+    messages: HashMap<String, OpenWebUiMessage>,
+    #[serde(rename = "currentId")]
+    current_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OpenWebUiMessage {
+    id: Option<String>,
+    #[serde(rename = "parentId")]
+    parent_id: Option<String>,
+    #[serde(rename = "childrenIds", default)]
+    children_ids: Vec<String>,
+    role: String,
+    #[serde(default)]
+    content: String,
+    timestamp: Option<f64>,
+    model: Option<String>,
+    #[serde(default)]
+    files: Vec<Value>,
+}
+
+fn parse_open_webui(chat: OpenWebUiChat) -> ImportedConversation {
+    let created_at = chat.created_at
+        .or(chat.chat.timestamp)
+        .and_then(epoch_to_timestamp)
+        .unwrap_or_else(now_timestamp);
+    let title = chat.title.or(chat.chat.title);
+
+    let to_imported = |message: OpenWebUiMessage| -> Option<ImportedMessage> {
+        if !matches!(message.role.as_str(), "user" | "assistant" | "system") {
+            return None;
+        }
+        let images: Vec<ImportedImage> = message.files.iter()
+            .filter(|file| file.get("type").and_then(Value::as_str) == Some("image"))
+            .filter_map(|file| file.get("url").and_then(Value::as_str).and_then(decode_data_url))
+            .collect();
+        if message.content.trim().is_empty() && images.is_empty() {
+            return None;
+        }
+
+        let timestamp = message.timestamp.and_then(epoch_to_timestamp).unwrap_or_else(|| created_at.clone());
+        let mut imported = ImportedMessage::text(&message.role, message.content, timestamp);
+        imported.model = message.model;
+        imported.images = images;
+        Some(imported)
+    };
+
+    let (history, current_id) = match chat.chat.history {
+        Some(history) if !history.messages.is_empty() => (history.messages, history.current_id),
+        _ => {
+            // Older exports: a flat list, each message answering the one before
+            let mut previous: Option<String> = None;
+            let mut messages: HashMap<String, OpenWebUiMessage> = HashMap::new();
+            for (index, mut message) in chat.chat.messages.into_iter().enumerate() {
+                let id = message.id.clone().unwrap_or_else(|| format!("message-{}", index));
+                message.parent_id = previous.clone();
+                if let Some(previous) = &previous {
+                    if let Some(parent) = messages.get_mut(previous) {
+                        parent.children_ids.push(id.clone());
+                    }
+                }
+                previous = Some(id.clone());
+                messages.insert(id, message);
+            }
+            (messages, previous)
+        }
+    };
+
+    let nodes = history.into_iter()
+        .map(|(id, message)| {
+            let parent = message.parent_id.clone();
+            let children = message.children_ids.clone();
+            (id, TreeNode { parent, children, message: to_imported(message) })
+        })
+        .collect();
+
+    let (messages, active_leaf) = flatten_tree(nodes, current_id.as_deref());
+    ImportedConversation {
+        title,
+        created_at,
+        messages,
+        active_leaf,
+    }
+}
+
+struct TreeNode {
+    parent: Option<String>,
+    children: Vec<String>,
+    message: Option<ImportedMessage>,
+}
+
+/// Orders a keyed message tree so parents come first, attaching the replies
+/// of skipped nodes to their nearest kept ancestor. Returns the messages and
+/// the index standing in for `current` (the branch that was being shown).
+fn flatten_tree(mut nodes: HashMap<String, TreeNode>, current: Option<&str>) -> (Vec<ImportedMessage>, Option<usize>) {
+    let mut roots: Vec<String> = nodes.iter()
+        .filter(|(_, node)| !matches!(&node.parent, Some(parent) if nodes.contains_key(parent)))
+        .map(|(id, _)| id.clone())
+        .collect();
+    roots.sort();
+
+    let mut messages = Vec::new();
+    let mut indices: HashMap<String, Option<usize>> = HashMap::new();
+    let mut visited = HashSet::new();
+    let mut stack: Vec<(String, Option<usize>)> = roots.into_iter().rev().map(|id| (id, None)).collect();
+
+    while let Some((id, parent_index)) = stack.pop() {
+        if !visited.insert(id.clone()) {
+            continue;
+        }
+        let Some(node) = nodes.remove(&id) else {
+            continue;
+        };
+
+        let index = match node.message {
+            Some(mut message) => {
+                message.parent = parent_index;
+                messages.push(message);
+                Some(messages.len() - 1)
+            }
+            None => parent_index,
+        };
+        indices.insert(id, index);
+        for child in node.children.into_iter().rev() {
+            stack.push((child, index));
+        }
+    }
+
+    let active_leaf = current.and_then(|id| indices.get(id).copied().flatten());
+    (messages, active_leaf)
+}
+
+fn decode_data_url(url: &str) -> Option<ImportedImage> {
+    let (header, data) = url.strip_prefix("data:")?.split_once(',')?;
+    let mime_type = header.strip_suffix(";base64")?;
+    let extension = match mime_type {
+        "image/png" => "png",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/jpeg" | "image/jpg" => "jpg",
+        _ => return None,
+    };
+    let bytes = general_purpose::STANDARD.decode(data).ok()?;
+    Some(ImportedImage {
+        filename: format!("image.{}", extension),
+        extension: extension.to_string(),
+        bytes,
+    })
+}
+
+// Exports use Unix seconds; some Open WebUI versions use milliseconds
+fn epoch_to_timestamp(seconds: f64) -> Option<String> {
+    let seconds = if seconds > 1e11 { seconds / 1000.0 } else { seconds };
+    chrono::DateTime::from_timestamp(seconds.trunc() as i64, 0)
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+fn now_timestamp() -> String {
+    chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
@@ -1,36 +1,811 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod backup;
+mod cancel;
+mod context;
 mod db;
+mod embeddings;
+mod encryption;
+mod export;
+mod history;
+mod importer;
+mod migrations;
 mod ollama;
+mod openai;
+mod provider;
+mod reasoning;
+mod search;
+mod structured;
+mod titles;
+mod tools;
 
-use db::{Database, Conversation, Message};
-use ollama::{OllamaClient, ChatMessage, OllamaModel};
+use backup::{BackupManifest, RestoreReport, StagedRestore};
+use cancel::CancelRegistry;
+use context::{ContextPolicy, ContextWindow, Fitted, TokenEstimator};
+use db::{Attachment, Database, Conversation, ConversationSummary, Endpoint, Message, ModelUsage, Persona, SearchFilters, SearchHit, MESSAGE_STATUS_CANCELLED};
+use ollama::{OllamaClient, ChatMessage, ChatResponse, ChatStreamEvent, GenerationOptions, ModelInfo, OllamaModel, PullProgress, ReplyMetrics, RunningModel, ToolDefinition};
+use std::collections::HashSet;
 use std::sync::Mutex;
-use tauri::State;
+use tauri::ipc::Channel;
+use tools::ToolRegistry;
+use provider::{ChatProvider, Provider, ProviderKind};
+use reasoning::ReplyPart;
+use std::collections::BTreeMap;
+use tauri::{AppHandle, Emitter, Manager, State};
 use chrono::Utc;
 use std::path::PathBuf;
 use std::fs;
 use base64::{Engine as _, engine::general_purpose};
+use embeddings::{EmbeddingFailure, EmbeddingStatus, SemanticMatch, EMBEDDING_STATUS_EVENT};
+use encryption::{EncryptionReport, EncryptionStatus, ImageKey, RemovalFailure, RemovalReport};
+use export::{ConversationExport, ExportFormat, ExportedMessage};
+use importer::ImportReport;
+use std::time::Duration;
+use tokio::sync::Notify;
+use titles::{TitleUpdated, TITLE_UPDATED_EVENT};
 
 struct AppState {
     db: Mutex<Database>,
-    ollama: OllamaClient,
+    // Client for the selected endpoint; swapped by `select_endpoint`
+    provider: Mutex<Provider>,
+    // In-flight generations keyed by conversation id
+    generations: CancelRegistry<i32>,
+    // In-flight model pulls keyed by model name
+    pulls: CancelRegistry<String>,
+    tools: ToolRegistry,
+    // Wakes the background embedding worker when new messages are saved
+    embedding_notify: Notify,
+    // Wakes the worker paused on an unknown model: a model was pulled or
+    // another endpoint selected, either of which may provide it
+    embedding_retry: Notify,
+    embedding_status: Mutex<EmbeddingStatus>,
+    // Conversations with a title request in flight
+    titling: Mutex<HashSet<i32>>,
 }
 
+// How often the embedding worker retries when nothing has woken it
+const EMBEDDING_POLL_INTERVAL: Duration = Duration::from_secs(60);
+// Wait after a failed batch, doubled for each failure in a row up to the max
+const EMBEDDING_RETRY_DELAY: Duration = Duration::from_secs(5);
+const MAX_EMBEDDING_RETRY_DELAY: Duration = Duration::from_secs(30 * 60);
+// How long the worker stays paused on an unknown model with no other cue
+const UNKNOWN_EMBEDDING_MODEL_RECHECK: Duration = Duration::from_secs(60 * 60);
+
+// Upper bound on model round trips in one tool-calling exchange
+const MAX_TOOL_ROUNDS: usize = 8;
+
 #[tauri::command]
 async fn check_ollama(state: State<'_, AppState>) -> Result<bool, String> {
-    Ok(state.ollama.check_connection().await)
+    Ok(provider(&state)?.check_connection().await)
 }
 
 #[tauri::command]
 async fn send_prompt(prompt: String, model: String, state: State<'_, AppState>) -> Result<String, String> {
-    state.ollama.send_prompt(&prompt, &model).await
+    let message = ChatMessage {
+        role: "user".to_string(),
+        content: prompt,
+        ..Default::default()
+    };
+    provider(&state)?.chat(vec![message], &model, None, Vec::new(), None).await
+        .map(|response| response.message.content)
+}
+
+#[tauri::command]
+async fn send_prompt_with_history(
+    messages: Vec<ChatMessage>,
+    model: String,
+    conversation_id: Option<i32>,
+    state: State<'_, AppState>
+) -> Result<String, String> {
+    let provider = provider(&state)?;
+    let (messages, options) = {
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        (apply_persona(&db, conversation_id, messages)?, resolve_options(&db, conversation_id, &model)?)
+    };
+    let (messages, _) = fit_context(&state, &provider, conversation_id, messages, &model, options.as_ref()).await?;
+    provider.chat(messages, &model, options, Vec::new(), None).await
+        .map(|response| response.message.content)
+}
+
+#[tauri::command]
+async fn stream_prompt_with_history(
+    conversation_id: i32,
+    messages: Vec<ChatMessage>,
+    model: String,
+    on_event: Channel<ChatStreamEvent>,
+    state: State<'_, AppState>
+) -> Result<String, String> {
+    let response = stream_reply(&state, conversation_id, messages, &model, None, &on_event).await?;
+    Ok(response.message.content)
+}
+
+/// Streams and saves a reply to the conversation's active branch, rebuilt
+/// from the database so earlier images are sent along (see `history::build`).
+#[tauri::command]
+async fn stream_conversation_reply(
+    conversation_id: i32,
+    model: String,
+    on_event: Channel<ChatStreamEvent>,
+    app: AppHandle,
+    state: State<'_, AppState>
+) -> Result<String, String> {
+    let history = {
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        conversation_history(&db, conversation_id)?
+    };
+
+    let response = stream_reply(&state, conversation_id, history, &model, None, &on_event).await?;
+
+    {
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        db.save_chat_message(conversation_id, &response.message, Some(&model), Some(&response.metrics), &timestamp)
+            .map_err(|e| format!("Database error: {}", e))?;
+    }
+    state.embedding_notify.notify_one();
+    spawn_auto_title(&app, conversation_id, Some(model));
+
+    Ok(response.message.content)
+}
+
+/// Streams a reply for the conversation through `on_event`, applying its
+/// persona and options, with `overrides` taking precedence. A cancelled reply is saved as far as it got,
+/// even while the history was still being summarized, and reported as
+/// `GENERATION_CANCELLED`; a finished one is left to the caller.
+async fn stream_reply(
+    state: &AppState,
+    conversation_id: i32,
+    messages: Vec<ChatMessage>,
+    model: &str,
+    overrides: Option<GenerationOptions>,
+    on_event: &Channel<ChatStreamEvent>,
+) -> Result<ChatResponse, String> {
+    let provider = provider(state)?;
+    let (messages, endpoint_id, options) = {
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        let endpoint_id = db.get_selected_endpoint_id().map_err(|e| format!("Database error: {}", e))?;
+        (
+            apply_persona(&db, Some(conversation_id), messages)?,
+            endpoint_id,
+            [overrides, resolve_options(&db, Some(conversation_id), model)?]
+                .into_iter()
+                .flatten()
+                .reduce(GenerationOptions::or),
+        )
+    };
+    let mut registration = state.generations.register(conversation_id)?
+        .ok_or("A reply is already being generated for this conversation.")?;
+
+    // Summarizing the history can take as long as a reply, so it can be cancelled too
+    let fitted = tokio::select! {
+        fitted = fit_context(state, &provider, Some(conversation_id), messages, model, options.as_ref()) => Some(fitted?),
+        _ = registration.cancelled() => None,
+    };
+    let Some((messages, window)) = fitted else {
+        drop(registration);
+        save_cancelled_reply(state, conversation_id, model, String::new(), "")?;
+        return Err("GENERATION_CANCELLED".to_string());
+    };
+    let _ = on_event.send(ChatStreamEvent::Context { window });
+    let mut partial = String::new();
+    let mut partial_thinking = String::new();
+
+    // Dropping the losing branch drops the HTTP response, which stops Ollama generating
+    let result = tokio::select! {
+        result = provider.stream_chat(messages, model, options, |part, delta| {
+            // A closed channel only means the window went away; keep reading so the reply is still returned
+            let _ = match part {
+                ReplyPart::Content => {
+                    partial.push_str(delta);
+                    on_event.send(ChatStreamEvent::Delta { content: delta.to_string() })
+                }
+                ReplyPart::Thinking => {
+                    partial_thinking.push_str(delta);
+                    on_event.send(ChatStreamEvent::Thinking { content: delta.to_string() })
+                }
+            };
+        }) => Some(result),
+        _ = registration.cancelled() => None,
+    };
+    drop(registration);
+
+    let mut response = match result {
+        Some(response) => response?,
+        None => {
+            save_cancelled_reply(state, conversation_id, model, partial, &partial_thinking)?;
+            return Err("GENERATION_CANCELLED".to_string());
+        }
+    };
+
+    response.metrics.model_digest = provider.model_digest(&response.model).await;
+    {
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        db.set_conversation_provider(conversation_id, endpoint_id, provider.kind())
+            .map_err(|e| format!("Database error: {}", e))?;
+    }
+
+    let _ = on_event.send(ChatStreamEvent::Done {
+        model: response.model.clone(),
+        created_at: response.created_at.clone(),
+        done_reason: response.done_reason.clone(),
+        content: response.message.content.clone(),
+        thinking: response.message.thinking.clone(),
+        metrics: response.metrics.clone(),
+    });
+
+    Ok(response)
+}
+
+/// Saves what a cancelled reply got to, possibly nothing yet, so callers can
+/// count on a `GENERATION_CANCELLED` reply being on the active branch.
+fn save_cancelled_reply(state: &AppState, conversation_id: i32, model: &str, content: String, thinking: &str) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let partial = ChatMessage {
+        role: "assistant".to_string(),
+        content,
+        thinking: Some(thinking.trim().to_string()).filter(|thinking| !thinking.is_empty()),
+        ..Default::default()
+    };
+    db.save_message_with_status(conversation_id, &partial, MESSAGE_STATUS_CANCELLED, Some(model), &timestamp)
+        .map_err(|e| format!("Database error: {}", e))
+}
+
+/// Replaces an earlier user message with `content` on a new sibling branch,
+/// then streams and saves a reply to it. The old branch stays reachable
+/// through `list_message_siblings` and `switch_branch`.
+#[tauri::command]
+async fn edit_and_resend(
+    message_id: i32,
+    content: String,
+    model: String,
+    on_event: Channel<ChatStreamEvent>,
+    app: AppHandle,
+    state: State<'_, AppState>
+) -> Result<String, String> {
+    let (conversation_id, history) = {
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        let original = db.get_message(message_id).map_err(|e| format!("Database error: {}", e))?;
+        if original.role != "user" {
+            return Err("Only user messages can be edited.".to_string());
+        }
+
+        let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        db.branch_before(message_id).map_err(|e| format!("Database error: {}", e))?;
+        db.save_message_with_attachments(
+            original.conversation_id,
+            "user",
+            &content,
+            &original.input_type,
+            &original.attachments,
+            &timestamp
+        ).map_err(|e| format!("Database error: {}", e))?;
+
+        (original.conversation_id, conversation_history(&db, original.conversation_id)?)
+    };
+    state.embedding_notify.notify_one();
+
+    let response = match stream_reply(&state, conversation_id, history, &model, None, &on_event).await {
+        Ok(response) => response,
+        Err(e) => {
+            // The edit got no reply, so show the original branch again; the
+            // edit stays reachable as a sibling
+            if e != "GENERATION_CANCELLED" {
+                let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+                db.switch_branch(message_id).map_err(|e| format!("Database error: {}", e))?;
+            }
+            return Err(e);
+        }
+    };
+
+    {
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        db.save_chat_message(conversation_id, &response.message, Some(&model), Some(&response.metrics), &timestamp)
+            .map_err(|e| format!("Database error: {}", e))?;
+    }
+    state.embedding_notify.notify_one();
+    spawn_auto_title(&app, conversation_id, Some(model));
+
+    Ok(response.message.content)
+}
+
+/// Generates another version of an assistant reply from the same history,
+/// optionally with a different model or options. The new version is saved
+/// as a sibling of the original and becomes the active one.
+#[tauri::command]
+async fn regenerate_message(
+    message_id: i32,
+    model: Option<String>,
+    options: Option<GenerationOptions>,
+    on_event: Channel<ChatStreamEvent>,
+    app: AppHandle,
+    state: State<'_, AppState>
+) -> Result<String, String> {
+    let (conversation_id, history, model) = {
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        let original = db.get_message(message_id).map_err(|e| format!("Database error: {}", e))?;
+        if original.role != "assistant" {
+            return Err("Only assistant replies can be regenerated.".to_string());
+        }
+        let model = model.or(original.model)
+            .ok_or("No model recorded for this reply; choose one to regenerate with.")?;
+
+        db.branch_before(message_id).map_err(|e| format!("Database error: {}", e))?;
+        (original.conversation_id, conversation_history(&db, original.conversation_id)?, model)
+    };
+
+    let response = match stream_reply(&state, conversation_id, history, &model, options, &on_event).await {
+        Ok(response) => response,
+        Err(e) => {
+            // Nothing was saved, so put the original version back on screen
+            if e != "GENERATION_CANCELLED" {
+                let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+                db.switch_branch(message_id).map_err(|e| format!("Database error: {}", e))?;
+            }
+            return Err(e);
+        }
+    };
+
+    {
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        db.save_chat_message(conversation_id, &response.message, Some(&model), Some(&response.metrics), &timestamp)
+            .map_err(|e| format!("Database error: {}", e))?;
+    }
+    state.embedding_notify.notify_one();
+    spawn_auto_title(&app, conversation_id, Some(model));
+
+    Ok(response.message.content)
+}
+
+/// Makes one version of an assistant reply the active one; the versions are
+/// the reply's siblings, as listed by `list_message_siblings`.
+#[tauri::command]
+async fn select_message_version(message_id: i32, state: State<'_, AppState>) -> Result<Vec<Message>, String> {
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    let message = db.get_message(message_id).map_err(|e| format!("Database error: {}", e))?;
+    if message.role != "assistant" {
+        return Err("Only assistant replies have versions.".to_string());
+    }
+    db.switch_branch(message_id).map_err(|e| format!("Database error: {}", e))?;
+    db.get_messages(message.conversation_id).map_err(|e| format!("Database error: {}", e))
+}
+
+#[tauri::command]
+async fn list_message_siblings(message_id: i32, state: State<'_, AppState>) -> Result<Vec<Message>, String> {
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    db.get_message_siblings(message_id).map_err(|e| format!("Database error: {}", e))
+}
+
+/// Shows the branch through `message_id`; returns the new active branch.
+#[tauri::command]
+async fn switch_branch(message_id: i32, state: State<'_, AppState>) -> Result<Vec<Message>, String> {
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    let message = db.get_message(message_id).map_err(|e| format!("Database error: {}", e))?;
+    db.switch_branch(message_id).map_err(|e| format!("Database error: {}", e))?;
+    db.get_messages(message.conversation_id).map_err(|e| format!("Database error: {}", e))
+}
+
+/// Layers the conversation's options over its persona's defaults and then
+/// the model defaults, earlier layers winning field by field.
+fn resolve_options(db: &Database, conversation_id: Option<i32>, model: &str) -> Result<Option<GenerationOptions>, String> {
+    let (conversation_options, persona_options) = match conversation_id {
+        Some(id) => (
+            db.get_conversation_options(id).map_err(|e| format!("Database error: {}", e))?,
+            db.get_conversation_persona(id)
+                .map_err(|e| format!("Database error: {}", e))?
+                .and_then(|persona| persona.default_options),
+        ),
+        None => (None, None),
+    };
+    let model_options = db.get_model_options(model).map_err(|e| format!("Database error: {}", e))?;
+
+    Ok([conversation_options, persona_options, model_options]
+        .into_iter()
+        .flatten()
+        .reduce(GenerationOptions::or))
+}
+
+/// The conversation's active branch as chat history, with its most recent
+/// images attached up to the configured limit.
+fn conversation_history(db: &Database, conversation_id: i32) -> Result<Vec<ChatMessage>, String> {
+    let messages = db.get_messages(conversation_id).map_err(|e| format!("Database error: {}", e))?;
+    Ok(history::build(&messages, history_image_limit(db)?, db.image_key()))
+}
+
+fn history_image_limit(db: &Database) -> Result<usize, String> {
+    Ok(db.get_setting(history::HISTORY_IMAGE_LIMIT_KEY)
+        .map_err(|e| format!("Database error: {}", e))?
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(history::DEFAULT_HISTORY_IMAGE_LIMIT))
+}
+
+/// Which messages fit the model's context under the conversation's policy,
+/// before any summary is made. See `context::fit`.
+fn plan_context(
+    db: &Database,
+    conversation_id: Option<i32>,
+    messages: Vec<ChatMessage>,
+    options: Option<&GenerationOptions>,
+) -> Result<(Fitted, ContextWindow, Option<ConversationSummary>, TokenEstimator), String> {
+    let (policy, stored, summary) = match conversation_id {
+        Some(id) => {
+            let conversation = db.get_conversation(id).map_err(|e| format!("Database error: {}", e))?;
+            (
+                conversation.context_policy.unwrap_or_default(),
+                db.get_messages(id).map_err(|e| format!("Database error: {}", e))?,
+                db.get_conversation_summary(id).map_err(|e| format!("Database error: {}", e))?,
+            )
+        }
+        None => (ContextPolicy::default(), Vec::new(), None),
+    };
+
+    let (context_tokens, budget) = context::prompt_budget(options);
+    let estimator = TokenEstimator::calibrated(&stored);
+    let fitted = context::fit(context::match_stored(messages, &stored), policy, budget, &estimator);
+    let window = ContextWindow {
+        policy,
+        context_tokens,
+        estimated_tokens: fitted.estimated_tokens,
+        dropped_message_ids: fitted.dropped.iter().filter_map(|entry| entry.id).collect(),
+        dropped_count: fitted.dropped.len(),
+        summarized: false,
+        summary_error: None,
+    };
+    Ok((fitted, window, summary, estimator))
+}
+
+/// Cuts `messages` down to the model's context. Under the summary policy the
+/// dropped messages are replaced by a rolling summary, extended from the
+/// stored one when it covers an earlier part of the same branch. Messages
+/// not yet covered are summarized in runs that each fit the context, saving
+/// progress after each. A failed summary is reported in the window and the
+/// messages are simply dropped.
+async fn fit_context(
+    state: &AppState,
+    provider: &Provider,
+    conversation_id: Option<i32>,
+    messages: Vec<ChatMessage>,
+    model: &str,
+    options: Option<&GenerationOptions>,
+) -> Result<(Vec<ChatMessage>, ContextWindow), String> {
+    let (fitted, mut window, stored, estimator) = {
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        plan_context(&db, conversation_id, messages, options)?
+    };
+    if window.policy != ContextPolicy::Summary || fitted.dropped.is_empty() {
+        return Ok((fitted.messages, window));
+    }
+
+    let covered = stored.as_ref().and_then(|summary| {
+        fitted.dropped.iter().position(|entry| entry.id == Some(summary.through_message_id))
+    });
+    let summary = match (stored, covered) {
+        (Some(stored), Some(position)) if position + 1 == fitted.dropped.len() => stored.summary,
+        (stored, covered) => {
+            let mut summary = stored.filter(|_| covered.is_some()).map(|summary| summary.summary);
+            let pending = &fitted.dropped[covered.map_or(0, |position| position + 1)..];
+            let pending_messages: Vec<ChatMessage> = pending.iter().map(|entry| entry.message.clone()).collect();
+            let summary_options = context::summary_options(options);
+            let (_, budget) = context::prompt_budget(Some(&summary_options));
+
+            for batch in context::summary_batches(&pending_messages, budget, &estimator) {
+                let request = context::summary_request(summary.as_deref(), &pending_messages[batch.clone()]);
+                match provider.chat(request, model, Some(summary_options.clone()), Vec::new(), None).await {
+                    Ok(response) => summary = Some(response.message.content.trim().to_string()),
+                    Err(e) => {
+                        window.summary_error = Some(e);
+                        return Ok((fitted.messages, window));
+                    }
+                }
+                let through = pending[..batch.end].iter().rev().find_map(|entry| entry.id);
+                if let (Some(conversation_id), Some(through), Some(summary)) = (conversation_id, through, &summary) {
+                    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+                    let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+                    db.save_conversation_summary(conversation_id, through, summary, model, &timestamp)
+                        .map_err(|e| format!("Database error: {}", e))?;
+                }
+            }
+            summary.unwrap_or_default()
+        }
+    };
+
+    window.summarized = true;
+    Ok((context::with_summary(fitted.messages, &summary), window))
+}
+
+/// How the conversation's next reply from `model` would fit its context,
+/// so the frontend can mark the messages the model will not see.
+#[tauri::command]
+async fn get_context_window(conversation_id: i32, model: String, state: State<'_, AppState>) -> Result<ContextWindow, String> {
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    let history = apply_persona(&db, Some(conversation_id), conversation_history(&db, conversation_id)?)?;
+    let options = resolve_options(&db, Some(conversation_id), &model)?;
+    let (_, mut window, _, _) = plan_context(&db, Some(conversation_id), history, options.as_ref())?;
+    window.summarized = window.policy == ContextPolicy::Summary && window.dropped_count > 0;
+    Ok(window)
+}
+
+/// Sets what is left out when the conversation outgrows the model's
+/// context; `None` goes back to the default sliding window.
+#[tauri::command]
+async fn set_context_policy(conversation_id: i32, policy: Option<ContextPolicy>, state: State<'_, AppState>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    db.set_context_policy(conversation_id, policy)
+        .map_err(|e| format!("Database error: {}", e))
+}
+
+#[tauri::command]
+async fn get_history_image_limit(state: State<'_, AppState>) -> Result<usize, String> {
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    history_image_limit(&db)
+}
+
+/// Sets how many of the most recent images are resent with the history;
+/// 0 sends each image only with the message it was attached to.
+#[tauri::command]
+async fn set_history_image_limit(limit: usize, state: State<'_, AppState>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    db.set_setting(history::HISTORY_IMAGE_LIMIT_KEY, &limit.to_string())
+        .map_err(|e| format!("Database error: {}", e))
+}
+
+/// Prepends the conversation persona's system prompt unless the caller
+/// already supplied a system message.
+fn apply_persona(db: &Database, conversation_id: Option<i32>, mut messages: Vec<ChatMessage>) -> Result<Vec<ChatMessage>, String> {
+    let Some(conversation_id) = conversation_id else {
+        return Ok(messages);
+    };
+    if messages.first().is_some_and(|message| message.role == "system") {
+        return Ok(messages);
+    }
+
+    let persona = db.get_conversation_persona(conversation_id)
+        .map_err(|e| format!("Database error: {}", e))?;
+    if let Some(persona) = persona {
+        if !persona.system_prompt.trim().is_empty() {
+            messages.insert(0, ChatMessage {
+                role: "system".to_string(),
+                content: persona.system_prompt,
+                ..Default::default()
+            });
+        }
+    }
+    Ok(messages)
+}
+
+#[tauri::command]
+async fn get_conversation_options(conversation_id: i32, state: State<'_, AppState>) -> Result<Option<GenerationOptions>, String> {
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    db.get_conversation_options(conversation_id).map_err(|e| format!("Database error: {}", e))
+}
+
+#[tauri::command]
+async fn set_conversation_options(
+    conversation_id: i32,
+    options: Option<GenerationOptions>,
+    state: State<'_, AppState>
+) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    db.set_conversation_options(conversation_id, options.as_ref())
+        .map_err(|e| format!("Database error: {}", e))
+}
+
+#[tauri::command]
+async fn get_model_options(model: String, state: State<'_, AppState>) -> Result<Option<GenerationOptions>, String> {
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    db.get_model_options(&model).map_err(|e| format!("Database error: {}", e))
+}
+
+#[tauri::command]
+async fn set_model_options(model: String, options: Option<GenerationOptions>, state: State<'_, AppState>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    db.set_model_options(&model, options.as_ref())
+        .map_err(|e| format!("Database error: {}", e))
+}
+
+#[tauri::command]
+async fn list_personas(state: State<'_, AppState>) -> Result<Vec<Persona>, String> {
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    db.get_personas().map_err(|e| format!("Database error: {}", e))
+}
+
+#[tauri::command]
+async fn create_persona(
+    name: String,
+    system_prompt: String,
+    default_model: Option<String>,
+    default_options: Option<GenerationOptions>,
+    state: State<'_, AppState>
+) -> Result<i32, String> {
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    let created_at = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    db.create_persona(name.trim(), &system_prompt, default_model.as_deref(), default_options.as_ref(), &created_at)
+        .map_err(|e| format!("Database error: {}", e))
+}
+
+#[tauri::command]
+async fn update_persona(
+    id: i32,
+    name: String,
+    system_prompt: String,
+    default_model: Option<String>,
+    default_options: Option<GenerationOptions>,
+    state: State<'_, AppState>
+) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    db.update_persona(id, name.trim(), &system_prompt, default_model.as_deref(), default_options.as_ref())
+        .map_err(|e| format!("Database error: {}", e))
+}
+
+#[tauri::command]
+async fn delete_persona(id: i32, state: State<'_, AppState>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    db.delete_persona(id).map_err(|e| format!("Database error: {}", e))
+}
+
+#[tauri::command]
+async fn set_conversation_persona(conversation_id: i32, persona_id: Option<i32>, state: State<'_, AppState>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    db.set_conversation_persona(conversation_id, persona_id)
+        .map_err(|e| format!("Database error: {}", e))
+}
+
+fn provider(state: &AppState) -> Result<Provider, String> {
+    state.provider.lock()
+        .map(|provider| provider.clone())
+        .map_err(|e| format!("Provider lock error: {}", e))
+}
+
+/// The selected endpoint's client, for model management only Ollama offers.
+fn ollama_client(state: &AppState) -> Result<OllamaClient, String> {
+    provider(state)?.ollama().cloned()
+}
+
+/// Points every later request at `endpoint`, or the local default for `None`.
+fn use_endpoint(state: &AppState, endpoint: Option<&Endpoint>) -> Result<(), String> {
+    let provider = Provider::for_endpoint(endpoint)?;
+    *state.provider.lock().map_err(|e| format!("Provider lock error: {}", e))? = provider;
+    state.embedding_retry.notify_one();
+    Ok(())
+}
+
+/// Runs a tool-calling exchange: the model may request tools, whose results
+/// are appended as `tool` messages before asking again, until it answers
+/// without tool calls. Every step, including the final answer, is saved.
+#[tauri::command]
+async fn send_prompt_with_tools(
+    conversation_id: i32,
+    messages: Vec<ChatMessage>,
+    model: String,
+    app: AppHandle,
+    state: State<'_, AppState>
+) -> Result<String, String> {
+    let provider = provider(&state)?;
+    let (messages, endpoint_id, options) = {
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        let endpoint_id = db.get_selected_endpoint_id().map_err(|e| format!("Database error: {}", e))?;
+        (
+            apply_persona(&db, Some(conversation_id), messages)?,
+            endpoint_id,
+            resolve_options(&db, Some(conversation_id), &model)?,
+        )
+    };
+    let (mut messages, _) = fit_context(&state, &provider, Some(conversation_id), messages, &model, options.as_ref()).await?;
+
+    for _ in 0..MAX_TOOL_ROUNDS {
+        let mut response = provider.chat(messages.clone(), &model, options.clone(), state.tools.definitions(), None).await?;
+        response.metrics.model_digest = provider.model_digest(&response.model).await;
+        let reply = response.message;
+        let tool_calls = reply.tool_calls.clone().unwrap_or_default();
+
+        let tool_results: Vec<ChatMessage> = tool_calls.iter()
+            .map(|call| ChatMessage {
+                role: "tool".to_string(),
+                content: state.tools.call(&call.function.name, &call.function.arguments),
+                tool_name: Some(call.function.name.clone()),
+                ..Default::default()
+            })
+            .collect();
+
+        {
+            let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+            let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+            db.save_chat_message(conversation_id, &reply, Some(&model), Some(&response.metrics), &timestamp)
+                .map_err(|e| format!("Database error: {}", e))?;
+            for result in &tool_results {
+                db.save_chat_message(conversation_id, result, None, None, &timestamp)
+                    .map_err(|e| format!("Database error: {}", e))?;
+            }
+            if tool_results.is_empty() {
+                db.set_conversation_provider(conversation_id, endpoint_id, provider.kind())
+                    .map_err(|e| format!("Database error: {}", e))?;
+            }
+        }
+
+        if tool_results.is_empty() {
+            spawn_auto_title(&app, conversation_id, Some(model));
+            return Ok(reply.content);
+        }
+        messages.push(reply);
+        messages.extend(tool_results);
+    }
+
+    Err(format!("The model was still calling tools after {} rounds.", MAX_TOOL_ROUNDS))
+}
+
+/// Asks for a reply matching `schema`, sending it as Ollama's `format` and
+/// re-prompting with the validation errors until it conforms or
+/// `max_attempts` is reached. Only the validated reply is saved.
+#[tauri::command]
+async fn send_structured_prompt(
+    conversation_id: i32,
+    messages: Vec<ChatMessage>,
+    model: String,
+    schema: serde_json::Value,
+    max_attempts: Option<u32>,
+    app: AppHandle,
+    state: State<'_, AppState>
+) -> Result<serde_json::Value, String> {
+    let validator = structured::compile_schema(&schema)?;
+    let max_attempts = max_attempts.unwrap_or(structured::DEFAULT_MAX_ATTEMPTS).max(1);
+
+    let provider = provider(&state)?;
+    let (messages, options) = {
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        (
+            apply_persona(&db, Some(conversation_id), messages)?,
+            resolve_options(&db, Some(conversation_id), &model)?,
+        )
+    };
+    let (mut messages, _) = fit_context(&state, &provider, Some(conversation_id), messages, &model, options.as_ref()).await?;
+
+    let mut errors = Vec::new();
+    for _ in 0..max_attempts {
+        let mut response = provider.chat(messages.clone(), &model, options.clone(), Vec::new(), Some(schema.clone())).await?;
+        let reply = response.message;
+
+        match structured::validate_reply(&validator, &reply.content) {
+            Ok(value) => {
+                let structured_content = serde_json::to_string(&value)
+                    .map_err(|e| format!("Failed to serialize structured reply: {}", e))?;
+                response.metrics.model_digest = provider.model_digest(&response.model).await;
+                let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+                let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+                db.save_structured_message(conversation_id, &reply, &structured_content, &model, Some(&response.metrics), &timestamp)
+                    .map_err(|e| format!("Database error: {}", e))?;
+                spawn_auto_title(&app, conversation_id, Some(model));
+                return Ok(value);
+            }
+            Err(attempt_errors) => {
+                messages.push(reply);
+                messages.push(ChatMessage {
+                    role: "user".to_string(),
+                    content: structured::correction_prompt(&attempt_errors),
+                    ..Default::default()
+                });
+                errors = attempt_errors;
+            }
+        }
+    }
+
+    Err(format!(
+        "Reply did not match the schema after {} attempts: {}",
+        max_attempts,
+        errors.join("; ")
+    ))
+}
+
+#[tauri::command]
+async fn list_tools(state: State<'_, AppState>) -> Result<Vec<ToolDefinition>, String> {
+    Ok(state.tools.definitions())
 }
 
 #[tauri::command]
-async fn send_prompt_with_history(messages: Vec<ChatMessage>, model: String, state: State<'_, AppState>) -> Result<String, String> {
-    state.ollama.send_prompt_with_history(messages, &model).await
+async fn cancel_generation(conversation_id: i32, state: State<'_, AppState>) -> Result<bool, String> {
+    state.generations.cancel(&conversation_id)
 }
 
 #[tauri::command]
@@ -45,37 +820,150 @@ async fn save_message(
     conversation_id: i32,
     role: String,
     content: String,
+    model: Option<String>,
+    metrics: Option<ReplyMetrics>,
+    app: AppHandle,
     state: State<'_, AppState>
 ) -> Result<(), String> {
     let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
     let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
-    db.save_message(conversation_id, &role, &content, &timestamp)
+    db.save_message(conversation_id, &role, &content, model.as_deref(), metrics.as_ref(), &timestamp)
+        .map_err(|e| format!("Database error: {}", e))?;
+    state.embedding_notify.notify_one();
+    if role == "assistant" {
+        spawn_auto_title(&app, conversation_id, model);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn rename_conversation(conversation_id: i32, title: Option<String>, state: State<'_, AppState>) -> Result<(), String> {
+    // A blank title clears it, letting the next exchange generate one again
+    let title = title.as_deref().map(str::trim).filter(|title| !title.is_empty());
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    db.rename_conversation(conversation_id, title)
         .map_err(|e| format!("Database error: {}", e))
 }
 
+/// Deletes a conversation with its messages and images. Returns the image
+/// files that could not be removed; `cleanup_images` catches them later.
+#[tauri::command]
+async fn delete_conversation(conversation_id: i32, state: State<'_, AppState>) -> Result<Vec<RemovalFailure>, String> {
+    // Stop any reply still streaming in; its partial save would find the conversation gone
+    state.generations.cancel(&conversation_id)?;
+    {
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        db.delete_conversation(conversation_id)
+            .map_err(|e| format!("Database error: {}", e))?;
+    }
+
+    // Only touch the disk once the rows are gone
+    let conv_dir = get_images_dir()?.join(conversation_id.to_string());
+    let mut failed = Vec::new();
+    if conv_dir.exists() {
+        if let Err(e) = fs::remove_dir_all(&conv_dir) {
+            failed.push(RemovalFailure { path: conv_dir.to_string_lossy().to_string(), error: e.to_string() });
+        }
+    }
+    Ok(failed)
+}
+
+/// Deletes a message and the replies below it. Returns the image files that
+/// could not be removed.
+#[tauri::command]
+async fn delete_message(message_id: i32, state: State<'_, AppState>) -> Result<Vec<RemovalFailure>, String> {
+    let orphaned_images = {
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        db.delete_message(message_id)
+            .map_err(|e| format!("Database error: {}", e))?
+    };
+
+    let mut failed = Vec::new();
+    for image_path in orphaned_images {
+        if let Err(e) = fs::remove_file(&image_path) {
+            failed.push(RemovalFailure { path: image_path, error: e.to_string() });
+        }
+    }
+    Ok(failed)
+}
+
+/// Names an untitled conversation from its first exchange in the background,
+/// using the model that replied, and emits `TITLE_UPDATED_EVENT` once stored.
+fn spawn_auto_title(app: &AppHandle, conversation_id: i32, model: Option<String>) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<AppState>();
+        let claimed = state.titling.lock()
+            .map(|mut titling| titling.insert(conversation_id))
+            .unwrap_or(false);
+        if !claimed {
+            return;
+        }
+        let result = generate_title(&state, conversation_id, model).await;
+        if let Ok(mut titling) = state.titling.lock() {
+            titling.remove(&conversation_id);
+        }
+
+        // An untitled conversation is tried again after its next reply
+        if let Ok(Some(title)) = result {
+            let _ = app.emit(TITLE_UPDATED_EVENT, TitleUpdated { conversation_id, title });
+        }
+    });
+}
+
+async fn generate_title(state: &AppState, conversation_id: i32, model: Option<String>) -> Result<Option<String>, String> {
+    let (request, model) = {
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        let conversation = db.get_conversation(conversation_id).map_err(|e| format!("Database error: {}", e))?;
+        if conversation.title.is_some() {
+            return Ok(None);
+        }
+        let Some(model) = model.or(conversation.model) else {
+            return Ok(None);
+        };
+        let messages = db.get_messages(conversation_id).map_err(|e| format!("Database error: {}", e))?;
+        let Some((user, assistant)) = titles::first_exchange(&messages) else {
+            return Ok(None);
+        };
+        (titles::title_request(user, assistant), model)
+    };
+
+    let response = provider(state)?
+        .chat(request, &model, Some(titles::title_options()), Vec::new(), None)
+        .await?;
+    let Some(title) = titles::clean_title(&response.message.content) else {
+        return Ok(None);
+    };
+
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    let stored = db.set_generated_title(conversation_id, &title)
+        .map_err(|e| format!("Database error: {}", e))?;
+    Ok(stored.then_some(title))
+}
+
+/// Saves a message with any number of images already written by
+/// `save_image_file`.
 #[tauri::command]
-async fn save_message_with_image(
+async fn save_message_with_attachments(
     conversation_id: i32,
     role: String,
     content: String,
     input_type: String,
-    image_path: Option<String>,
-    image_filename: Option<String>,
-    image_size: Option<i32>,
+    attachments: Vec<Attachment>,
     state: State<'_, AppState>
 ) -> Result<(), String> {
     let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
     let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
-    db.save_message_with_image(
-        conversation_id, 
-        &role, 
-        &content, 
+    db.save_message_with_attachments(
+        conversation_id,
+        &role,
+        &content,
         &input_type,
-        image_path.as_deref(),
-        image_filename.as_deref(),
-        image_size,
+        &attachments,
         &timestamp
-    ).map_err(|e| format!("Database error: {}", e))
+    ).map_err(|e| format!("Database error: {}", e))?;
+    state.embedding_notify.notify_one();
+    Ok(())
 }
 
 #[tauri::command]
@@ -83,7 +971,7 @@ async fn save_image_file(
     conversation_id: i32,
     image_data: String,
     filename: String,
-    _state: State<'_, AppState>
+    state: State<'_, AppState>
 ) -> Result<String, String> {
     // Create images directory
     let images_dir = get_images_dir()?;
@@ -93,8 +981,8 @@ async fn save_image_file(
     let conv_dir = images_dir.join(conversation_id.to_string());
     fs::create_dir_all(&conv_dir).map_err(|e| format!("Failed to create conversation directory: {}", e))?;
     
-    // Generate unique filename
-    let timestamp = Utc::now().format("%Y%m%d_%H%M%S").to_string();
+    // Generate unique filename; a message's images are saved within the same second
+    let timestamp = Utc::now().format("%Y%m%d_%H%M%S_%f").to_string();
     let extension = std::path::Path::new(&filename)
         .extension()
         .and_then(|ext| ext.to_str())
@@ -107,16 +995,19 @@ async fn save_image_file(
         .decode(&image_data)
         .map_err(|e| format!("Failed to decode base64: {}", e))?;
     
-    fs::write(&file_path, image_bytes)
-        .map_err(|e| format!("Failed to write image file: {}", e))?;
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    encryption::write_image(&file_path, &image_bytes, db.image_key())?;
     
     Ok(file_path.to_string_lossy().to_string())
 }
 
 #[tauri::command]
-async fn get_image_base64(image_path: String) -> Result<String, String> {
-    let image_bytes = fs::read(&image_path)
-        .map_err(|e| format!("Failed to read image file: {}", e))?;
+async fn get_image_base64(image_path: String, state: State<'_, AppState>) -> Result<String, String> {
+    let image_key = {
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        db.image_key().copied()
+    };
+    let image_bytes = encryption::read_image(std::path::Path::new(&image_path), image_key.as_ref())?;
     
     Ok(general_purpose::STANDARD.encode(image_bytes))
 }
@@ -128,8 +1019,15 @@ async fn send_prompt_with_image(
     model: String,
     state: State<'_, AppState>
 ) -> Result<String, String> {
-    let image_base64 = get_image_base64(image_path).await?;
-    state.ollama.send_prompt_with_image(&prompt, &image_base64, &model).await
+    let image_base64 = get_image_base64(image_path, state.clone()).await?;
+    let message = ChatMessage {
+        role: "user".to_string(),
+        content: prompt,
+        images: Some(vec![image_base64]),
+        ..Default::default()
+    };
+    provider(&state)?.chat(vec![message], &model, None, Vec::new(), None).await
+        .map(|response| response.message.content)
 }
 
 #[tauri::command]
@@ -152,45 +1050,744 @@ async fn get_messages(conversation_id: i32, state: State<'_, AppState>) -> Resul
     db.get_messages(conversation_id).map_err(|e| format!("Database error: {}", e))
 }
 
+/// Token usage and generation speed per model for replies between `from`
+/// and `to` (`YYYY-MM-DD`, both inclusive; either may be left open).
+#[tauri::command]
+async fn get_usage_stats(from: Option<String>, to: Option<String>, state: State<'_, AppState>) -> Result<Vec<ModelUsage>, String> {
+    let to = to.as_deref().map(search::end_of_day);
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    db.get_usage_stats(from.as_deref(), to.as_deref()).map_err(|e| format!("Database error: {}", e))
+}
+
 #[tauri::command]
 async fn list_models(state: State<'_, AppState>) -> Result<Vec<OllamaModel>, String> {
-    state.ollama.list_models().await
+    provider(&state)?.list_models().await
 }
 
-fn get_images_dir() -> Result<PathBuf, String> {
+#[tauri::command]
+async fn pull_model(
+    model: String,
+    on_progress: Channel<PullProgress>,
+    state: State<'_, AppState>
+) -> Result<Vec<OllamaModel>, String> {
+    let ollama = ollama_client(&state)?;
+    let mut registration = state.pulls.register(model.clone())?
+        .ok_or(format!("'{}' is already being pulled.", model))?;
+
+    let result = tokio::select! {
+        result = ollama.pull_model(&model, |progress| {
+            let _ = on_progress.send(progress.clone());
+        }) => Some(result),
+        _ = registration.cancelled() => None,
+    };
+    drop(registration);
+
+    match result {
+        Some(result) => result?,
+        None => return Err("PULL_CANCELLED".to_string()),
+    }
+    state.embedding_retry.notify_one();
+
+    ollama.list_models().await
+}
+
+#[tauri::command]
+async fn cancel_pull(model: String, state: State<'_, AppState>) -> Result<bool, String> {
+    state.pulls.cancel(&model)
+}
+
+#[tauri::command]
+async fn show_model(model: String, state: State<'_, AppState>) -> Result<ModelInfo, String> {
+    ollama_client(&state)?.show_model(&model).await
+}
+
+#[tauri::command]
+async fn copy_model(source: String, destination: String, state: State<'_, AppState>) -> Result<Vec<OllamaModel>, String> {
+    let ollama = ollama_client(&state)?;
+    ollama.copy_model(&source, &destination).await?;
+    ollama.list_models().await
+}
+
+#[tauri::command]
+async fn delete_model(model: String, state: State<'_, AppState>) -> Result<Vec<OllamaModel>, String> {
+    let ollama = ollama_client(&state)?;
+    ollama.delete_model(&model).await?;
+    ollama.list_models().await
+}
+
+#[tauri::command]
+async fn list_running_models(state: State<'_, AppState>) -> Result<Vec<RunningModel>, String> {
+    ollama_client(&state)?.list_running_models().await
+}
+
+#[tauri::command]
+async fn list_endpoints(state: State<'_, AppState>) -> Result<Vec<Endpoint>, String> {
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    db.get_endpoints().map_err(|e| format!("Database error: {}", e))
+}
+
+/// Adds an endpoint. `provider` defaults to Ollama; OpenAI-compatible
+/// endpoints take a base URL ending in the version, e.g. `/v1`.
+#[tauri::command]
+async fn add_endpoint(
+    name: String,
+    base_url: String,
+    provider: Option<ProviderKind>,
+    api_key: Option<String>,
+    headers: Option<BTreeMap<String, String>>,
+    state: State<'_, AppState>
+) -> Result<i32, String> {
+    let created_at = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let endpoint = endpoint_settings(0, &name, &base_url, provider, api_key, headers, created_at)?;
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    db.add_endpoint(&endpoint)
+        .map_err(|e| format!("Database error: {}", e))
+}
+
+#[tauri::command]
+async fn update_endpoint(
+    id: i32,
+    name: String,
+    base_url: String,
+    provider: Option<ProviderKind>,
+    api_key: Option<String>,
+    headers: Option<BTreeMap<String, String>>,
+    state: State<'_, AppState>
+) -> Result<(), String> {
+    let endpoint = endpoint_settings(id, &name, &base_url, provider, api_key, headers, String::new())?;
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    db.update_endpoint(&endpoint)
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    if db.get_selected_endpoint_id().map_err(|e| format!("Database error: {}", e))? == Some(id) {
+        use_endpoint(&state, Some(&endpoint))?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn test_endpoint(
+    base_url: String,
+    provider: Option<ProviderKind>,
+    api_key: Option<String>,
+    headers: Option<BTreeMap<String, String>>,
+) -> Result<bool, String> {
+    let endpoint = endpoint_settings(0, "", &base_url, provider, api_key, headers, String::new())?;
+    Ok(Provider::for_endpoint(Some(&endpoint))?.check_connection().await)
+}
+
+#[tauri::command]
+async fn select_endpoint(id: i32, state: State<'_, AppState>) -> Result<(), String> {
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    let endpoint = db.get_endpoint(id).map_err(|e| format!("Database error: {}", e))?;
+    db.select_endpoint(id).map_err(|e| format!("Database error: {}", e))?;
+    use_endpoint(&state, Some(&endpoint))
+}
+
+/// Validates endpoint settings from the frontend, rejecting headers that
+/// could not be sent before anything is stored.
+fn endpoint_settings(
+    id: i32,
+    name: &str,
+    base_url: &str,
+    provider: Option<ProviderKind>,
+    api_key: Option<String>,
+    headers: Option<BTreeMap<String, String>>,
+    created_at: String,
+) -> Result<Endpoint, String> {
+    let api_key = api_key.map(|key| key.trim().to_string()).filter(|key| !key.is_empty());
+    let headers = headers.unwrap_or_default();
+    provider::http_client(api_key.as_deref(), &headers)?;
+
+    Ok(Endpoint {
+        id,
+        name: name.trim().to_string(),
+        base_url: normalize_endpoint_url(base_url)?,
+        provider: provider.unwrap_or(ProviderKind::Ollama),
+        api_key,
+        headers,
+        created_at,
+        is_selected: false,
+    })
+}
+
+fn normalize_endpoint_url(base_url: &str) -> Result<String, String> {
+    let base_url = base_url.trim().trim_end_matches('/');
+    if !base_url.starts_with("http://") && !base_url.starts_with("https://") {
+        return Err("Endpoint URL must start with http:// or https://".to_string());
+    }
+    Ok(base_url.to_string())
+}
+
+#[tauri::command]
+async fn search_messages(
+    query: String,
+    filters: Option<SearchFilters>,
+    limit: Option<usize>,
+    state: State<'_, AppState>
+) -> Result<Vec<SearchHit>, String> {
+    let Some(match_query) = search::build_match_query(&query) else {
+        return Ok(Vec::new());
+    };
+    let mut filters = filters.unwrap_or_default();
+    filters.to = filters.to.as_deref().map(search::end_of_day);
+
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    db.search_messages(&match_query, &filters, limit.unwrap_or(50))
+        .map_err(|e| format!("Database error: {}", e))
+}
+
+#[tauri::command]
+async fn semantic_search(query: String, top_k: Option<usize>, state: State<'_, AppState>) -> Result<Vec<SemanticMatch>, String> {
+    let model = {
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        embedding_model(&db)?
+    };
+    let query_vector = provider(&state)?.embed(&model, vec![query]).await?
+        .pop()
+        .ok_or("No embedding returned for the query")?;
+
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    let candidates = db.get_embeddings(&model).map_err(|e| format!("Database error: {}", e))?;
+    let ranked = embeddings::rank(&query_vector, candidates, top_k.unwrap_or(10));
+
+    let mut matches = Vec::new();
+    for (message_id, score) in ranked {
+        let message = db.get_message(message_id).map_err(|e| format!("Database error: {}", e))?;
+        let conversation = db.get_conversation(message.conversation_id).map_err(|e| format!("Database error: {}", e))?;
+        matches.push(SemanticMatch { score, message, conversation });
+    }
+    Ok(matches)
+}
+
+#[tauri::command]
+async fn get_embedding_model(state: State<'_, AppState>) -> Result<String, String> {
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    embedding_model(&db)
+}
+
+#[tauri::command]
+async fn set_embedding_model(model: String, state: State<'_, AppState>) -> Result<(), String> {
+    {
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        db.set_setting(embeddings::EMBEDDING_MODEL_KEY, model.trim())
+            .map_err(|e| format!("Database error: {}", e))?;
+    }
+    // Existing history needs vectors from the new model
+    state.embedding_notify.notify_one();
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_embedding_status(state: State<'_, AppState>) -> Result<EmbeddingStatus, String> {
+    let status = state.embedding_status.lock().map_err(|e| format!("Embedding status lock error: {}", e))?;
+    Ok(status.clone())
+}
+
+fn embedding_model(db: &Database) -> Result<String, String> {
+    Ok(db.get_setting(embeddings::EMBEDDING_MODEL_KEY)
+        .map_err(|e| format!("Database error: {}", e))?
+        .unwrap_or_else(|| embeddings::DEFAULT_EMBEDDING_MODEL.to_string()))
+}
+
+/// Embeds messages in the background: catches up on history at startup, then
+/// again whenever a message is saved or the poll interval elapses. Failed
+/// batches are retried with exponential backoff; a model the server does
+/// not have waits for a likely fix instead. Each change of state is emitted as
+/// `EMBEDDING_STATUS_EVENT`.
+async fn run_embedding_worker(app: AppHandle) {
+    let state = app.state::<AppState>();
+    let mut failures = 0;
+    loop {
+        match embed_pending_messages(&state).await {
+            Ok(()) => {
+                failures = 0;
+                report_embedding_status(&app, EmbeddingStatus::Ready);
+                tokio::select! {
+                    _ = state.embedding_notify.notified() => {}
+                    _ = tokio::time::sleep(EMBEDDING_POLL_INTERVAL) => {}
+                }
+            }
+            Err(EmbeddingFailure::UnknownModel { model, error }) => {
+                failures = 0;
+                report_embedding_status(&app, EmbeddingStatus::UnknownModel { model: model.clone(), error });
+                wait_for_unknown_model_fix(&state, &model).await;
+            }
+            Err(EmbeddingFailure::Failed(error)) => {
+                let delay = EMBEDDING_RETRY_DELAY.saturating_mul(1 << failures.min(16)).min(MAX_EMBEDDING_RETRY_DELAY);
+                failures += 1;
+                report_embedding_status(&app, EmbeddingStatus::Retrying { error, retry_in_secs: delay.as_secs() });
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+}
+
+fn report_embedding_status(app: &AppHandle, status: EmbeddingStatus) {
+    let state = app.state::<AppState>();
+    let Ok(mut current) = state.embedding_status.lock() else {
+        return;
+    };
+    if *current != status {
+        *current = status.clone();
+        let _ = app.emit(EMBEDDING_STATUS_EVENT, status);
+    }
+}
+
+// Returns once something may have made the missing `model` available, or
+// the embedding model setting is something else
+async fn wait_for_unknown_model_fix(state: &AppState, model: &str) {
+    let recheck = tokio::time::sleep(UNKNOWN_EMBEDDING_MODEL_RECHECK);
+    tokio::pin!(recheck);
+    loop {
+        tokio::select! {
+            _ = &mut recheck => return,
+            _ = state.embedding_retry.notified() => return,
+            _ = state.embedding_notify.notified() => {
+                let current = state.db.lock().ok().and_then(|db| embedding_model(&db).ok());
+                if current.is_some_and(|current| current != model) {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+async fn embed_pending_messages(state: &AppState) -> Result<(), EmbeddingFailure> {
+    loop {
+        let (model, pending) = {
+            let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+            if db.encryption_status().locked {
+                return Ok(());
+            }
+            let model = embedding_model(&db)?;
+            let pending = db.get_unembedded_messages(&model, embeddings::EMBEDDING_BATCH_SIZE)
+                .map_err(|e| format!("Database error: {}", e))?;
+            (model, pending)
+        };
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let (message_ids, texts): (Vec<i32>, Vec<String>) = pending.into_iter().unzip();
+        let vectors = match provider(state)?.embed(&model, texts).await {
+            Ok(vectors) => vectors,
+            Err(error) if embeddings::is_unknown_model_error(&error) => {
+                return Err(EmbeddingFailure::UnknownModel { model, error });
+            }
+            Err(error) => return Err(EmbeddingFailure::Failed(error)),
+        };
+
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        for (message_id, vector) in message_ids.iter().zip(&vectors) {
+            db.save_embedding(*message_id, &model, vector)
+                .map_err(|e| format!("Database error: {}", e))?;
+        }
+    }
+}
+
+/// Writes one conversation as Markdown, JSON or standalone HTML to
+/// `destination`, or to the exports folder when none is given, and returns
+/// the path written. Markdown links images unless `embed_images` is set.
+/// Model reasoning is included unless `include_reasoning` is false.
+#[tauri::command]
+async fn export_conversation(
+    conversation_id: i32,
+    format: ExportFormat,
+    destination: Option<String>,
+    embed_images: Option<bool>,
+    include_reasoning: Option<bool>,
+    state: State<'_, AppState>
+) -> Result<String, String> {
+    let (exports, image_key) = {
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        let export = conversation_export(&db, conversation_id, format, include_reasoning.unwrap_or(true))?;
+        (vec![export], db.image_key().copied())
+    };
+    let default_name = format!("conversation-{}", conversation_id);
+    write_export(exports, image_key, format, destination, embed_images.unwrap_or(false), &default_name)
+}
+
+/// Like `export_conversation`, with every conversation in a single file.
+#[tauri::command]
+async fn export_all(
+    format: ExportFormat,
+    destination: Option<String>,
+    embed_images: Option<bool>,
+    include_reasoning: Option<bool>,
+    state: State<'_, AppState>
+) -> Result<String, String> {
+    let (exports, image_key) = {
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        let conversations = db.get_conversations().map_err(|e| format!("Database error: {}", e))?;
+        let exports = conversations.iter()
+            .map(|conversation| conversation_export(&db, conversation.id, format, include_reasoning.unwrap_or(true)))
+            .collect::<Result<Vec<_>, String>>()?;
+        (exports, db.image_key().copied())
+    };
+    write_export(exports, image_key, format, destination, embed_images.unwrap_or(false), "conversations")
+}
+
+// JSON keeps every branch so it can be imported again; the readable formats show the active one
+fn conversation_export(db: &Database, conversation_id: i32, format: ExportFormat, include_reasoning: bool) -> Result<ConversationExport, String> {
+    let conversation = db.get_conversation(conversation_id).map_err(|e| format!("Database error: {}", e))?;
+    let messages = match format {
+        ExportFormat::Json => db.get_all_messages(conversation_id),
+        ExportFormat::Markdown | ExportFormat::Html => db.get_messages(conversation_id),
+    }.map_err(|e| format!("Database error: {}", e))?;
+
+    Ok(ConversationExport {
+        conversation,
+        messages: messages.into_iter()
+            .map(|mut message| {
+                if !include_reasoning {
+                    message.reasoning = None;
+                }
+                ExportedMessage::new(message)
+            })
+            .collect(),
+    })
+}
+
+fn write_export(
+    mut exports: Vec<ConversationExport>,
+    image_key: Option<ImageKey>,
+    format: ExportFormat,
+    destination: Option<String>,
+    embed_images: bool,
+    default_name: &str,
+) -> Result<String, String> {
+    if format != ExportFormat::Markdown || embed_images {
+        export::load_images(&mut exports, image_key.as_ref());
+    }
+
+    let now = Utc::now();
+    let content = export::render(exports, format, embed_images, &now.format("%Y-%m-%d %H:%M:%S").to_string())?;
+
+    let path = match destination {
+        Some(destination) => PathBuf::from(destination),
+        None => {
+            let exports_dir = get_exports_dir()?;
+            fs::create_dir_all(&exports_dir).map_err(|e| format!("Failed to create exports directory: {}", e))?;
+            exports_dir.join(format!("{}-{}.{}", default_name, now.format("%Y%m%d_%H%M%S"), format.extension()))
+        }
+    };
+    fs::write(&path, content).map_err(|e| format!("Failed to write export: {}", e))?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Imports a ChatGPT `conversations.json`, an Open WebUI export or one of our
+/// own JSON exports, passed as the file's contents. Conversations already in
+/// the database are skipped.
+#[tauri::command]
+async fn import_conversations(contents: String, state: State<'_, AppState>) -> Result<ImportReport, String> {
+    let images_dir = get_images_dir()?;
+    let report = {
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        importer::import(&db, &contents, &images_dir)?
+    };
+    if report.messages_imported > 0 {
+        state.embedding_notify.notify_one();
+    }
+    Ok(report)
+}
+
+/// Zips a consistent snapshot of `chat.db` together with the images into
+/// `destination`, or into the backups folder when none is given, and returns
+/// the path written.
+#[tauri::command]
+async fn create_backup(destination: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let data_dir = get_data_dir()?;
+    let now = Utc::now();
+    let stamp = now.format("%Y%m%d_%H%M%S").to_string();
+    let snapshot = data_dir.join(format!("backup-{}.db.tmp", stamp));
+
+    let (schema_version, encrypted) = {
+        let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        db.backup_to(&snapshot).map_err(|e| format!("Database error: {}", e))?;
+        let schema_version = db.schema_version().map_err(|e| format!("Database error: {}", e))?;
+        (schema_version, db.encryption_status().encrypted)
+    };
+
+    let destination = match destination {
+        Some(destination) => PathBuf::from(destination),
+        None => {
+            let backups_dir = data_dir.join("backups");
+            fs::create_dir_all(&backups_dir).map_err(|e| format!("Failed to create backups directory: {}", e))?;
+            backups_dir.join(format!("chat-backup-{}.zip", stamp))
+        }
+    };
+    let images_dir = get_images_dir()?;
+    let manifest = BackupManifest {
+        format: backup::BACKUP_FORMAT_ID.to_string(),
+        version: backup::BACKUP_VERSION,
+        created_at: now.format("%Y-%m-%d %H:%M:%S").to_string(),
+        schema_version,
+        images_dir: images_dir.to_string_lossy().to_string(),
+        encrypted,
+    };
+
+    let result = backup::write_archive(&snapshot, &manifest, &images_dir, &destination);
+    let _ = fs::remove_file(&snapshot);
+    result?;
+    Ok(destination.to_string_lossy().to_string())
+}
+
+/// Replaces all data with a backup made by `create_backup`, possibly on
+/// another machine. The archive is unpacked and checked before anything is
+/// touched, and the replaced database and images are kept alongside.
+/// Encrypted backups need the passphrase they were made with.
+#[tauri::command]
+async fn restore_backup(path: String, passphrase: Option<String>, state: State<'_, AppState>) -> Result<RestoreReport, String> {
+    let data_dir = get_data_dir()?;
+    let stamp = Utc::now().format("%Y%m%d_%H%M%S").to_string();
+    let staging_dir = data_dir.join(format!("restore-{}", stamp));
+
+    let passphrase = passphrase.as_deref();
+    let result = match backup::stage_restore(std::path::Path::new(&path), &staging_dir, migrations::latest_version(), passphrase) {
+        Ok(staged) => apply_restore(&state, &staged, backup::staged_passphrase(&staged.manifest, passphrase), &data_dir, &stamp),
+        Err(e) => Err(e),
+    };
+    let _ = fs::remove_dir_all(&staging_dir);
+
+    if result.is_ok() {
+        state.embedding_notify.notify_one();
+    }
+    result
+}
+
+fn apply_restore(
+    state: &AppState,
+    staged: &StagedRestore,
+    passphrase: Option<&str>,
+    data_dir: &std::path::Path,
+    stamp: &str,
+) -> Result<RestoreReport, String> {
+    let images_dir = get_images_dir()?;
+    backup::rewrite_image_paths(&staged.database, &staged.manifest.images_dir, &images_dir, passphrase)?;
+
+    let previous_database = data_dir.join(format!("chat.pre-restore-{}.bak", stamp));
+    let mut db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    db.backup_to(&previous_database).map_err(|e| format!("Failed to save the current database: {}", e))?;
+
+    // Images go first, so the database is only replaced once the paths it
+    // points at exist. A failed `restore_from` keeps the current database, and
+    // any failure puts the current images back.
+    let previous_images = if images_dir.exists() {
+        let previous_images = data_dir.join(format!("images.pre-restore-{}", stamp));
+        fs::rename(&images_dir, &previous_images).map_err(|e| format!("Failed to move current images aside: {}", e))?;
+        Some(previous_images)
+    } else {
+        None
+    };
+    let put_back_images = |restored: bool| {
+        if restored {
+            let _ = fs::rename(&images_dir, &staged.images_dir);
+        }
+        if let Some(previous_images) = &previous_images {
+            let _ = fs::rename(previous_images, &images_dir);
+        }
+    };
+    if let Err(e) = fs::rename(&staged.images_dir, &images_dir) {
+        put_back_images(false);
+        return Err(format!("Failed to restore images: {}", e));
+    }
+    if let Err(e) = db.restore_from(&staged.database, passphrase) {
+        put_back_images(true);
+        return Err(format!("Failed to restore database: {}", e));
+    }
+
+    let endpoint = db.get_selected_endpoint().map_err(|e| format!("Database error: {}", e))?;
+    let conversations = db.get_conversations().map_err(|e| format!("Database error: {}", e))?.len();
+    drop(db);
+
+    use_endpoint(state, endpoint.as_ref())?;
+
+    Ok(RestoreReport {
+        backup_created_at: staged.manifest.created_at.clone(),
+        conversations,
+        images: staged.image_count,
+        previous_database: previous_database.to_string_lossy().to_string(),
+        previous_images: previous_images.map(|path| path.to_string_lossy().to_string()),
+        encrypted: staged.manifest.encrypted,
+    })
+}
+
+#[tauri::command]
+async fn get_encryption_status(state: State<'_, AppState>) -> Result<EncryptionStatus, String> {
+    let db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    Ok(db.encryption_status())
+}
+
+/// Opens an encrypted database with the passphrase entered at startup.
+/// Images left plain by an interrupted `enable_encryption` are finished off.
+#[tauri::command]
+async fn unlock_database(passphrase: String, state: State<'_, AppState>) -> Result<(), String> {
+    let endpoint = {
+        let mut db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+        if !db.unlock(&passphrase).map_err(|e| format!("Database error: {}", e))? {
+            return Err("Wrong passphrase.".to_string());
+        }
+        if let Some(image_key) = db.image_key() {
+            encryption::encrypt_images_in_dir(&get_images_dir()?, image_key)?;
+        }
+        db.get_selected_endpoint().map_err(|e| format!("Database error: {}", e))?
+    };
+
+    use_endpoint(&state, endpoint.as_ref())?;
+    state.embedding_notify.notify_one();
+    Ok(())
+}
+
+/// Encrypts the database in place with SQLCipher and every image with a key
+/// stored inside it. The plain copies kept by migrations and restores are
+/// listed, to be removed with `remove_plain_copies` once confirmed.
+#[tauri::command]
+async fn enable_encryption(passphrase: String, state: State<'_, AppState>) -> Result<EncryptionReport, String> {
+    if passphrase.is_empty() {
+        return Err("Passphrase must not be empty.".to_string());
+    }
+    let mut db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    db.enable_encryption(&passphrase).map_err(|e| format!("Failed to encrypt database: {}", e))?;
+
+    let image_key = db.image_key().ok_or("Encrypted database has no image key")?;
+    let images_encrypted = encryption::encrypt_images_in_dir(&get_images_dir()?, image_key)?;
+    let plain_copies = encryption::plain_copies(&get_data_dir()?);
+    Ok(EncryptionReport { images_encrypted, plain_copies })
+}
+
+/// Deletes unencrypted copies listed by `enable_encryption` once the user
+/// has confirmed them, reporting any that could not be removed.
+#[tauri::command]
+async fn remove_plain_copies(paths: Vec<String>) -> Result<RemovalReport, String> {
+    Ok(encryption::remove_plain_copies(&get_data_dir()?, &paths))
+}
+
+/// Changes the passphrase of an encrypted database. Images are unaffected.
+#[tauri::command]
+async fn change_passphrase(current: String, new: String, state: State<'_, AppState>) -> Result<(), String> {
+    if new.is_empty() {
+        return Err("Passphrase must not be empty.".to_string());
+    }
+    let mut db = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
+    if !db.change_passphrase(&current, &new).map_err(|e| format!("Database error: {}", e))? {
+        return Err("Wrong passphrase.".to_string());
+    }
+    Ok(())
+}
+
+fn get_data_dir() -> Result<PathBuf, String> {
     let mut path = dirs::data_local_dir()
-        .or_else(|| dirs::home_dir())
+        .or_else(dirs::home_dir)
         .ok_or("Failed to get data directory")?;
-    
+
     path.push("com.example.chat");
-    path.push("images");
     Ok(path)
 }
 
+fn get_exports_dir() -> Result<PathBuf, String> {
+    Ok(get_data_dir()?.join("exports"))
+}
+
+fn get_images_dir() -> Result<PathBuf, String> {
+    Ok(get_data_dir()?.join("images"))
+}
+
 fn main() {
     let database = Database::new().expect("Failed to initialize database");
-    let ollama_client = OllamaClient::new();
+    // An encrypted database is read once unlocked; `unlock_database` swaps in the right client
+    let endpoint = if database.encryption_status().locked {
+        None
+    } else {
+        database.get_selected_endpoint().expect("Failed to load endpoint")
+    };
+    let provider = Provider::for_endpoint(endpoint.as_ref()).expect("Failed to create endpoint client");
     
     let app_state = AppState {
         db: Mutex::new(database),
-        ollama: ollama_client,
+        provider: Mutex::new(provider),
+        generations: CancelRegistry::new(),
+        pulls: CancelRegistry::new(),
+        tools: ToolRegistry::with_builtin_tools(),
+        embedding_notify: Notify::new(),
+        embedding_retry: Notify::new(),
+        embedding_status: Mutex::new(EmbeddingStatus::Ready),
+        titling: Mutex::new(HashSet::new()),
     };
 
     tauri::Builder::default()
         .manage(app_state)
+        .setup(|app| {
+            tauri::async_runtime::spawn(run_embedding_worker(app.handle().clone()));
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             check_ollama,
             send_prompt,
             send_prompt_with_history,
+            stream_prompt_with_history,
+            stream_conversation_reply,
+            edit_and_resend,
+            list_message_siblings,
+            switch_branch,
+            regenerate_message,
+            select_message_version,
+            send_prompt_with_tools,
+            list_tools,
+            send_structured_prompt,
+            cancel_generation,
             send_prompt_with_image,
             create_conversation,
+            rename_conversation,
+            delete_conversation,
+            delete_message,
             save_message,
-            save_message_with_image,
+            save_message_with_attachments,
             save_image_file,
             get_image_base64,
             get_conversations,
             get_messages,
+            get_usage_stats,
+            get_context_window,
+            set_context_policy,
+            get_history_image_limit,
+            set_history_image_limit,
             list_models,
+            pull_model,
+            cancel_pull,
+            show_model,
+            copy_model,
+            delete_model,
+            list_running_models,
+            get_conversation_options,
+            set_conversation_options,
+            get_model_options,
+            set_model_options,
+            list_personas,
+            create_persona,
+            update_persona,
+            delete_persona,
+            set_conversation_persona,
+            search_messages,
+            semantic_search,
+            get_embedding_model,
+            set_embedding_model,
+            get_embedding_status,
+            list_endpoints,
+            add_endpoint,
+            update_endpoint,
+            test_endpoint,
+            select_endpoint,
+            export_conversation,
+            export_all,
+            import_conversations,
+            create_backup,
+            restore_backup,
+            get_encryption_status,
+            unlock_database,
+            enable_encryption,
+            remove_plain_copies,
+            change_passphrase,
             cleanup_images
         ])
         .run(tauri::generate_context!())
@@ -0,0 +1,481 @@
+use rusqlite::{Connection, Result, Transaction};
+use std::path::Path;
+
+use crate::db::{DEFAULT_ENDPOINT_URL, SELECTED_ENDPOINT_KEY};
+
+struct Migration {
+    version: i32,
+    description: &'static str,
+    up: fn(&Transaction) -> Result<()>,
+}
+
+/// Schema history, oldest first. `PRAGMA user_version` records the last one
+/// applied; never edit or reorder an entry once released, only append.
+const MIGRATIONS: &[Migration] = &[
+    Migration { version: 1, description: "conversations and messages", up: base_schema },
+    Migration { version: 2, description: "message status", up: message_status },
+    Migration { version: 3, description: "endpoints and settings", up: endpoints },
+    Migration { version: 4, description: "generation options", up: generation_options },
+    Migration { version: 5, description: "personas", up: personas },
+    Migration { version: 6, description: "tool calls", up: tool_calls },
+    Migration { version: 7, description: "structured content", up: structured_content },
+    Migration { version: 8, description: "message embeddings", up: message_embeddings },
+    Migration { version: 9, description: "message model and full-text search", up: full_text_search },
+    Migration { version: 10, description: "conversation titles", up: conversation_titles },
+    Migration { version: 11, description: "cascading deletes", up: cascading_deletes },
+    Migration { version: 12, description: "message branches", up: message_branches },
+    Migration { version: 13, description: "endpoint providers", up: endpoint_providers },
+    Migration { version: 14, description: "reply metrics", up: reply_metrics },
+    Migration { version: 15, description: "attachments", up: attachments },
+    Migration { version: 16, description: "context policies", up: context_policies },
+    Migration { version: 17, description: "reasoning", up: reasoning },
+];
+
+pub fn latest_version() -> i32 {
+    MIGRATIONS.last().map(|migration| migration.version).unwrap_or(0)
+}
+
+/// Brings the database up to the latest schema. Each migration runs in its own
+/// transaction together with the `user_version` bump, and an existing database
+/// is copied aside first. A database written by a newer build is refused.
+pub fn run(conn: &mut Connection, db_path: &Path) -> Result<()> {
+    let current: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    let latest = latest_version();
+
+    if current > latest {
+        return Err(failure(format!(
+            "Database schema version {} is newer than this app supports ({}). Please update the app.",
+            current, latest
+        )));
+    }
+    if current == latest {
+        return Ok(());
+    }
+
+    if has_user_tables(conn)? {
+        backup(conn, db_path, current)?;
+    }
+
+    // Rebuilds drop tables that others reference, which enforced foreign keys would block
+    conn.pragma_update(None, "foreign_keys", false)?;
+    for migration in MIGRATIONS.iter().filter(|migration| migration.version > current) {
+        let tx = conn.transaction()?;
+        (migration.up)(&tx).map_err(|e| {
+            failure(format!("Migration {} ({}) failed: {}", migration.version, migration.description, e))
+        })?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn has_user_tables(conn: &Connection) -> Result<bool> {
+    conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// Writes a consistent copy next to the database, e.g. `chat.v3-20250101T120000.bak`.
+fn backup(conn: &Connection, db_path: &Path, version: i32) -> Result<()> {
+    let stem = db_path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("chat");
+    let timestamp = chrono::Utc::now().format("%Y%m%dT%H%M%S");
+    let backup_path = db_path.with_file_name(format!("{}.v{}-{}.bak", stem, version, timestamp));
+
+    conn.execute("VACUUM INTO ?1", [backup_path.to_string_lossy()])
+        .map_err(|e| failure(format!("Failed to back up database before migrating: {}", e)))?;
+    Ok(())
+}
+
+pub(crate) fn failure(message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_ERROR),
+        Some(message),
+    )
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let names = stmt.query_map([], |row| row.get::<_, String>(1))?;
+    for name in names {
+        if name? == column {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// Databases from before versioning may already have some of these columns
+fn add_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    if !has_column(conn, table, column)? {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+    }
+    Ok(())
+}
+
+fn base_schema(tx: &Transaction) -> Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS conversations (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            created_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS messages (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            conversation_id INTEGER,
+            role TEXT NOT NULL,
+            content TEXT NOT NULL,
+            timestamp TEXT NOT NULL,
+            FOREIGN KEY (conversation_id) REFERENCES conversations(id)
+        )",
+        [],
+    )?;
+
+    add_column(tx, "messages", "input_type", "TEXT DEFAULT 'text'")?;
+    add_column(tx, "messages", "image_path", "TEXT")?;
+    add_column(tx, "messages", "image_filename", "TEXT")?;
+    add_column(tx, "messages", "image_size", "INTEGER")?;
+    Ok(())
+}
+
+fn message_status(tx: &Transaction) -> Result<()> {
+    add_column(tx, "messages", "status", "TEXT NOT NULL DEFAULT 'complete'")
+}
+
+fn endpoints(tx: &Transaction) -> Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS endpoints (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            base_url TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    add_column(tx, "conversations", "endpoint_id", "INTEGER REFERENCES endpoints(id)")?;
+
+    // Seed the local server so a fresh install behaves as before
+    let endpoint_count: i32 = tx.query_row("SELECT COUNT(*) FROM endpoints", [], |row| row.get(0))?;
+    if endpoint_count == 0 {
+        let created_at = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        tx.execute(
+            "INSERT INTO endpoints (name, base_url, created_at) VALUES ('Local', ?1, ?2)",
+            [DEFAULT_ENDPOINT_URL, &created_at],
+        )?;
+        tx.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+            [SELECTED_ENDPOINT_KEY.to_string(), tx.last_insert_rowid().to_string()],
+        )?;
+    }
+    Ok(())
+}
+
+fn generation_options(tx: &Transaction) -> Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS model_options (
+            model TEXT PRIMARY KEY,
+            options TEXT NOT NULL
+        )",
+        [],
+    )?;
+    add_column(tx, "conversations", "options", "TEXT")
+}
+
+fn personas(tx: &Transaction) -> Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS personas (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            system_prompt TEXT NOT NULL,
+            default_model TEXT,
+            default_options TEXT,
+            created_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    add_column(tx, "conversations", "persona_id", "INTEGER REFERENCES personas(id)")
+}
+
+fn tool_calls(tx: &Transaction) -> Result<()> {
+    add_column(tx, "messages", "tool_calls", "TEXT")?;
+    add_column(tx, "messages", "tool_name", "TEXT")
+}
+
+fn structured_content(tx: &Transaction) -> Result<()> {
+    add_column(tx, "messages", "structured_content", "TEXT")
+}
+
+fn message_embeddings(tx: &Transaction) -> Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS message_embeddings (
+            message_id INTEGER PRIMARY KEY,
+            model TEXT NOT NULL,
+            dimensions INTEGER NOT NULL,
+            vector BLOB NOT NULL,
+            FOREIGN KEY (message_id) REFERENCES messages(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn full_text_search(tx: &Transaction) -> Result<()> {
+    add_column(tx, "messages", "model", "TEXT")?;
+
+    tx.execute_batch(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
+            content,
+            content = 'messages',
+            content_rowid = 'id',
+            tokenize = 'unicode61 remove_diacritics 2'
+        );
+
+        CREATE TRIGGER IF NOT EXISTS messages_fts_insert AFTER INSERT ON messages BEGIN
+            INSERT INTO messages_fts (rowid, content) VALUES (new.id, new.content);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS messages_fts_delete AFTER DELETE ON messages BEGIN
+            INSERT INTO messages_fts (messages_fts, rowid, content) VALUES ('delete', old.id, old.content);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS messages_fts_update AFTER UPDATE OF content ON messages BEGIN
+            INSERT INTO messages_fts (messages_fts, rowid, content) VALUES ('delete', old.id, old.content);
+            INSERT INTO messages_fts (rowid, content) VALUES (new.id, new.content);
+        END;
+
+        INSERT INTO messages_fts (messages_fts) VALUES ('rebuild');"
+    )
+}
+
+// The triggers of migrations 9 and 10. They live with the `messages` table,
+// so a rebuild of it must recreate them.
+const MESSAGES_FTS_TRIGGERS: &str = "
+    CREATE TRIGGER IF NOT EXISTS messages_fts_insert AFTER INSERT ON messages BEGIN
+        INSERT INTO messages_fts (rowid, content) VALUES (new.id, new.content);
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS messages_fts_delete AFTER DELETE ON messages BEGIN
+        INSERT INTO messages_fts (messages_fts, rowid, content) VALUES ('delete', old.id, old.content);
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS messages_fts_update AFTER UPDATE OF content ON messages BEGIN
+        INSERT INTO messages_fts (messages_fts, rowid, content) VALUES ('delete', old.id, old.content);
+        INSERT INTO messages_fts (rowid, content) VALUES (new.id, new.content);
+    END;";
+
+const CONVERSATIONS_TOUCH_TRIGGER: &str = "
+    CREATE TRIGGER IF NOT EXISTS conversations_touch AFTER INSERT ON messages BEGIN
+        UPDATE conversations SET
+            updated_at = MAX(COALESCE(updated_at, ''), new.timestamp),
+            model = COALESCE(new.model, model)
+        WHERE id = new.conversation_id;
+    END;";
+
+fn conversation_titles(tx: &Transaction) -> Result<()> {
+    add_column(tx, "conversations", "title", "TEXT")?;
+    add_column(tx, "conversations", "updated_at", "TEXT")?;
+    add_column(tx, "conversations", "model", "TEXT")?;
+
+    tx.execute_batch(
+        "UPDATE conversations SET
+            updated_at = COALESCE(
+                (SELECT MAX(m.timestamp) FROM messages m WHERE m.conversation_id = conversations.id),
+                created_at
+            ),
+            model = (
+                SELECT m.model FROM messages m
+                WHERE m.conversation_id = conversations.id AND m.model IS NOT NULL
+                ORDER BY m.timestamp DESC, m.id DESC LIMIT 1
+            );
+
+        CREATE TRIGGER IF NOT EXISTS conversations_touch AFTER INSERT ON messages BEGIN
+            UPDATE conversations SET
+                updated_at = MAX(COALESCE(updated_at, ''), new.timestamp),
+                model = COALESCE(new.model, model)
+            WHERE id = new.conversation_id;
+        END;"
+    )
+}
+
+/// SQLite cannot add `ON DELETE CASCADE` to an existing foreign key, so
+/// `messages` and `message_embeddings` are rebuilt. Messages whose
+/// conversation is missing are kept in recovered conversations, so the
+/// constraints hold once `PRAGMA foreign_keys` is on; dangling endpoint and
+/// persona links are unset.
+fn cascading_deletes(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "INSERT INTO conversations (id, created_at, updated_at, title)
+            SELECT conversation_id, MIN(timestamp), MAX(timestamp), 'Recovered messages' FROM messages
+            WHERE conversation_id IS NOT NULL AND conversation_id NOT IN (SELECT id FROM conversations)
+            GROUP BY conversation_id;
+        INSERT INTO conversations (created_at, updated_at, title)
+            SELECT MIN(timestamp), MAX(timestamp), 'Recovered messages' FROM messages
+            WHERE conversation_id IS NULL
+            HAVING COUNT(*) > 0;
+        UPDATE messages SET conversation_id = last_insert_rowid() WHERE conversation_id IS NULL;
+        UPDATE conversations SET endpoint_id = NULL
+            WHERE endpoint_id IS NOT NULL AND endpoint_id NOT IN (SELECT id FROM endpoints);
+        UPDATE conversations SET persona_id = NULL
+            WHERE persona_id IS NOT NULL AND persona_id NOT IN (SELECT id FROM personas);
+
+        CREATE TABLE messages_new (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            conversation_id INTEGER NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+            role TEXT NOT NULL,
+            content TEXT NOT NULL,
+            input_type TEXT DEFAULT 'text',
+            image_path TEXT,
+            image_filename TEXT,
+            image_size INTEGER,
+            timestamp TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'complete',
+            tool_calls TEXT,
+            tool_name TEXT,
+            structured_content TEXT,
+            model TEXT
+        );
+        INSERT INTO messages_new (id, conversation_id, role, content, input_type, image_path, image_filename,
+                image_size, timestamp, status, tool_calls, tool_name, structured_content, model)
+            SELECT id, conversation_id, role, content, input_type, image_path, image_filename,
+                image_size, timestamp, status, tool_calls, tool_name, structured_content, model
+            FROM messages;
+        DROP TABLE messages;
+        ALTER TABLE messages_new RENAME TO messages;
+        CREATE INDEX idx_messages_conversation ON messages(conversation_id);
+
+        CREATE TABLE message_embeddings_new (
+            message_id INTEGER PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+            model TEXT NOT NULL,
+            dimensions INTEGER NOT NULL,
+            vector BLOB NOT NULL
+        );
+        INSERT INTO message_embeddings_new (message_id, model, dimensions, vector)
+            SELECT message_id, model, dimensions, vector FROM message_embeddings;
+        DROP TABLE message_embeddings;
+        ALTER TABLE message_embeddings_new RENAME TO message_embeddings;"
+    )?;
+    tx.execute_batch(MESSAGES_FTS_TRIGGERS)?;
+    tx.execute_batch(CONVERSATIONS_TOUCH_TRIGGER)
+}
+
+/// Turns each conversation into a tree: every message points at the one it
+/// answers, and the conversation remembers the leaf of the branch on screen.
+/// Existing history becomes a single branch in timestamp order.
+fn message_branches(tx: &Transaction) -> Result<()> {
+    add_column(tx, "messages", "parent_id", "INTEGER REFERENCES messages(id) ON DELETE CASCADE")?;
+    add_column(tx, "conversations", "active_leaf_id", "INTEGER REFERENCES messages(id) ON DELETE SET NULL")?;
+
+    tx.execute_batch(
+        "UPDATE messages SET parent_id = (
+            SELECT previous.id FROM messages previous
+            WHERE previous.conversation_id = messages.conversation_id
+              AND (previous.timestamp < messages.timestamp
+                   OR (previous.timestamp = messages.timestamp AND previous.id < messages.id))
+            ORDER BY previous.timestamp DESC, previous.id DESC LIMIT 1
+        );
+
+        UPDATE conversations SET active_leaf_id = (
+            SELECT m.id FROM messages m WHERE m.conversation_id = conversations.id
+            ORDER BY m.timestamp DESC, m.id DESC LIMIT 1
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_messages_parent ON messages(conversation_id, parent_id);
+
+        -- New messages continue the active branch and become its leaf
+        CREATE TRIGGER IF NOT EXISTS messages_append_to_branch AFTER INSERT ON messages BEGIN
+            UPDATE messages SET parent_id = (
+                SELECT active_leaf_id FROM conversations WHERE id = new.conversation_id
+            ) WHERE id = new.id;
+            UPDATE conversations SET active_leaf_id = new.id WHERE id = new.conversation_id;
+        END;"
+    )
+}
+
+/// Lets an endpoint speak the OpenAI protocol instead of Ollama's, with an
+/// API key and extra headers, and records on each conversation which
+/// protocol answered it. Everything so far was Ollama.
+fn endpoint_providers(tx: &Transaction) -> Result<()> {
+    add_column(tx, "endpoints", "provider", "TEXT NOT NULL DEFAULT 'ollama'")?;
+    add_column(tx, "endpoints", "api_key", "TEXT")?;
+    // JSON object of header name to value
+    add_column(tx, "endpoints", "headers", "TEXT")?;
+    add_column(tx, "conversations", "provider", "TEXT")?;
+
+    tx.execute_batch(
+        "UPDATE conversations SET provider = 'ollama'
+         WHERE endpoint_id IS NOT NULL
+            OR EXISTS (SELECT 1 FROM messages WHERE conversation_id = conversations.id AND role = 'assistant');"
+    )
+}
+
+/// Token counts and timings for each assistant reply, and the digest of the
+/// model build that produced it. Durations are in nanoseconds.
+fn reply_metrics(tx: &Transaction) -> Result<()> {
+    add_column(tx, "messages", "total_duration", "INTEGER")?;
+    add_column(tx, "messages", "load_duration", "INTEGER")?;
+    add_column(tx, "messages", "prompt_eval_count", "INTEGER")?;
+    add_column(tx, "messages", "eval_count", "INTEGER")?;
+    add_column(tx, "messages", "eval_duration", "INTEGER")?;
+    add_column(tx, "messages", "model_digest", "TEXT")?;
+
+    tx.execute_batch("CREATE INDEX IF NOT EXISTS idx_messages_model_timestamp ON messages(model, timestamp);")
+}
+
+/// Moves images from the single `image_path` on each message into their own
+/// table, so a message can carry several. The old image columns are cleared
+/// and no longer read.
+fn attachments(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS attachments (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            message_id INTEGER NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+            position INTEGER NOT NULL,
+            path TEXT NOT NULL,
+            filename TEXT,
+            size INTEGER
+        );
+        CREATE INDEX IF NOT EXISTS idx_attachments_message ON attachments(message_id, position);
+        CREATE INDEX IF NOT EXISTS idx_attachments_path ON attachments(path);
+
+        INSERT INTO attachments (message_id, position, path, filename, size)
+            SELECT id, 0, image_path, image_filename, image_size FROM messages WHERE image_path IS NOT NULL;
+        UPDATE messages SET image_path = NULL, image_filename = NULL, image_size = NULL
+            WHERE image_path IS NOT NULL;"
+    )
+}
+
+/// How each conversation is cut down to the model's context, and the rolling
+/// summary of what was cut. A summary covers its branch up to and including
+/// `through_message_id`.
+fn context_policies(tx: &Transaction) -> Result<()> {
+    add_column(tx, "conversations", "context_policy", "TEXT")?;
+
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS conversation_summaries (
+            conversation_id INTEGER PRIMARY KEY REFERENCES conversations(id) ON DELETE CASCADE,
+            through_message_id INTEGER NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+            summary TEXT NOT NULL,
+            model TEXT,
+            created_at TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_conversation_summaries_message ON conversation_summaries(through_message_id);"
+    )
+}
+
+/// Reasoning from thinking models, kept apart from the answer in `content`.
+fn reasoning(tx: &Transaction) -> Result<()> {
+    add_column(tx, "messages", "reasoning", "TEXT")
+}
@@ -1,13 +1,98 @@
 use reqwest;
+use futures_util::StreamExt;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use crate::context::ContextWindow;
+use crate::provider::{chat_http_error, ChatProvider};
+use crate::reasoning::{self, ReplyPart, StreamedReply};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub role: String,
     pub content: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub images: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    /// Set on `role: "tool"` messages to name the tool whose result this is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+    /// Reasoning a thinking model produced before its answer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<String>,
+}
+
+/// A function the model may call, described by a JSON Schema for its arguments.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolDefinition {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: ToolFunction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolFunction {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub function: ToolCallFunction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallFunction {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: serde_json::Value,
+}
+
+/// Sampling and context parameters sent as Ollama's `options` object. Unset
+/// fields fall back to the model's own defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GenerationOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat_penalty: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    /// Whether a thinking model should reason first. Not a model option:
+    /// Ollama takes it beside `options`, see `ChatRequest::new`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub think: Option<bool>,
+}
+
+impl GenerationOptions {
+    /// Fills any field left unset here from `defaults`.
+    pub fn or(self, defaults: GenerationOptions) -> GenerationOptions {
+        GenerationOptions {
+            temperature: self.temperature.or(defaults.temperature),
+            top_p: self.top_p.or(defaults.top_p),
+            top_k: self.top_k.or(defaults.top_k),
+            num_ctx: self.num_ctx.or(defaults.num_ctx),
+            num_predict: self.num_predict.or(defaults.num_predict),
+            repeat_penalty: self.repeat_penalty.or(defaults.repeat_penalty),
+            seed: self.seed.or(defaults.seed),
+            stop: self.stop.or(defaults.stop),
+            think: self.think.or(defaults.think),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -15,12 +100,126 @@ pub struct ChatRequest {
     pub model: String,
     pub messages: Vec<ChatMessage>,
     pub stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<GenerationOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<ToolDefinition>>,
+    /// `"json"` or a JSON Schema the reply must conform to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub think: Option<bool>,
+}
+
+impl ChatRequest {
+    /// Builds a request, moving `think` out of `options` to the top level.
+    pub fn new(
+        model: &str,
+        messages: Vec<ChatMessage>,
+        stream: bool,
+        mut options: Option<GenerationOptions>,
+        tools: Vec<ToolDefinition>,
+        format: Option<serde_json::Value>,
+    ) -> Self {
+        let think = options.as_mut().and_then(|options| options.think.take());
+        ChatRequest {
+            model: model.to_string(),
+            messages,
+            stream,
+            options,
+            tools: if tools.is_empty() { None } else { Some(tools) },
+            format,
+            think,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ChatResponse {
+    #[serde(default)]
+    pub model: String,
+    #[serde(default)]
+    pub created_at: String,
     pub message: ChatMessage,
     pub done: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub done_reason: Option<String>,
+    #[serde(flatten)]
+    pub metrics: ReplyMetrics,
+}
+
+/// Token counts and timings reported with the final response, stored on the
+/// assistant message. Durations are in nanoseconds.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReplyMetrics {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_duration: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load_duration: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_eval_count: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eval_count: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eval_duration: Option<i64>,
+    /// Not part of the chat response; looked up from the model list afterwards.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_digest: Option<String>,
+}
+
+impl ReplyMetrics {
+    pub fn is_empty(&self) -> bool {
+        self.total_duration.is_none()
+            && self.load_duration.is_none()
+            && self.prompt_eval_count.is_none()
+            && self.eval_count.is_none()
+            && self.eval_duration.is_none()
+            && self.model_digest.is_none()
+    }
+}
+
+/// Error object Ollama writes in place of a chunk when a stream fails midway.
+#[derive(Debug, Deserialize)]
+struct StreamError {
+    error: String,
+}
+
+/// Payload pushed to the frontend over the chat stream channel.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "camelCase")]
+pub enum ChatStreamEvent {
+    /// Sent before the first delta: what of the history the model gets.
+    Context { window: ContextWindow },
+    #[serde(rename_all = "camelCase")]
+    Delta { content: String },
+    /// A fragment of the model's reasoning, kept apart from the answer.
+    #[serde(rename_all = "camelCase")]
+    Thinking { content: String },
+    #[serde(rename_all = "camelCase")]
+    Done {
+        model: String,
+        created_at: String,
+        done_reason: Option<String>,
+        content: String,
+        thinking: Option<String>,
+        metrics: ReplyMetrics,
+    },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelDetails {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub family: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub families: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameter_size: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantization_level: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -29,6 +228,8 @@ pub struct OllamaModel {
     pub size: i64,
     pub digest: String,
     pub modified_at: String,
+    #[serde(default)]
+    pub details: ModelDetails,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -36,169 +237,281 @@ pub struct ModelsResponse {
     pub models: Vec<OllamaModel>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShowRequest {
+    pub model: String,
+}
+
+/// Response of `/api/show`. `model_info` is kept as raw JSON because its keys
+/// are architecture specific (e.g. `llama.context_length`).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ModelInfo {
+    #[serde(default)]
+    pub modelfile: String,
+    #[serde(default)]
+    pub parameters: String,
+    #[serde(default)]
+    pub template: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    #[serde(default)]
+    pub details: ModelDetails,
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_info: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CopyRequest {
+    pub source: String,
+    pub destination: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteRequest {
+    pub model: String,
+}
+
+/// A model currently loaded into memory, as reported by `/api/ps`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunningModel {
+    pub name: String,
+    #[serde(default)]
+    pub model: String,
+    pub size: i64,
+    #[serde(default)]
+    pub size_vram: i64,
+    pub digest: String,
+    #[serde(default)]
+    pub details: ModelDetails,
+    pub expires_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunningModelsResponse {
+    pub models: Vec<RunningModel>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbedRequest {
+    pub model: String,
+    pub input: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbedResponse {
+    pub embeddings: Vec<Vec<f32>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PullRequest {
+    pub model: String,
+    pub stream: bool,
+}
+
+/// One status line from `/api/pull`. Download lines carry the layer digest
+/// and byte counts; the last line has status `success`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullProgress {
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed: Option<u64>,
+}
+
+#[derive(Clone)]
 pub struct OllamaClient {
     client: reqwest::Client,
     base_url: String,
+    // Per model, whether its chat template opens a `<think>` block in the
+    // prompt. Shared by clones, which every request uses.
+    opened_thinking: Arc<Mutex<HashMap<String, bool>>>,
 }
 
 impl OllamaClient {
-    pub fn new() -> Self {
+    pub fn new(base_url: &str) -> Self {
         let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(120))
+            // Idle timeout rather than a total one, so long streamed replies aren't cut off
+            .read_timeout(Duration::from_secs(120))
             .build()
             .unwrap();
         
+        Self::with_http_client(base_url, client)
+    }
+
+    /// Uses a client built by `provider::http_client`, e.g. to send an API
+    /// key to an Ollama server behind an authenticating proxy.
+    pub fn with_http_client(base_url: &str, client: reqwest::Client) -> Self {
         Self {
             client,
-            base_url: "http://localhost:11434".to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            opened_thinking: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
-    pub async fn check_connection(&self) -> bool {
-        match self.client.get(&self.base_url).send().await {
-            Ok(response) => response.status().is_success(),
-            Err(_) => false,
+    /// Pulls a model from the registry, calling `on_progress` for every status
+    /// line Ollama reports until the pull succeeds.
+    pub async fn pull_model<F>(&self, model: &str, mut on_progress: F) -> Result<(), String>
+    where
+        F: FnMut(&PullProgress),
+    {
+        let pull_request = PullRequest {
+            model: model.to_string(),
+            stream: true,
+        };
+
+        let url = format!("{}/api/pull", self.base_url);
+
+        let response = match self.client.post(&url).json(&pull_request).send().await {
+            Ok(response) => response,
+            Err(e) => return Err(self.describe_request_error(e)),
+        };
+
+        if !response.status().is_success() {
+            return Err(http_error(response).await);
+        }
+
+        let mut succeeded = false;
+        read_ndjson(response, |progress: PullProgress| {
+            on_progress(&progress);
+            succeeded = progress.status == "success";
+            succeeded
+        }, |e| self.describe_request_error(e)).await?;
+
+        if succeeded {
+            Ok(())
+        } else {
+            Err(format!("Pull of '{}' ended before it completed.", model))
         }
     }
 
-    pub async fn send_prompt(&self, prompt: &str, model: &str) -> Result<String, String> {
-        let chat_request = ChatRequest {
+    /// Embeds each input with `model`, returning one vector per input in order.
+    pub async fn embed(&self, model: &str, input: Vec<String>) -> Result<Vec<Vec<f32>>, String> {
+        let url = format!("{}/api/embed", self.base_url);
+        let expected = input.len();
+        let embed_request = EmbedRequest {
             model: model.to_string(),
-            messages: vec![ChatMessage {
-                role: "user".to_string(),
-                content: prompt.to_string(),
-                images: None,
-            }],
-            stream: false,
+            input,
         };
 
-        let url = format!("{}/api/chat", self.base_url);
-        
-        match self.client
-            .post(&url)
-            .json(&chat_request)
-            .send()
-            .await
-        {
-            Ok(response) => {
-                if response.status().is_success() {
-                    match response.json::<ChatResponse>().await {
-                        Ok(chat_response) => Ok(chat_response.message.content),
-                        Err(e) => Err(format!("Failed to parse response: {}", e)),
-                    }
-                } else {
-                    let status = response.status();
-                    let error_text = response.text().await.unwrap_or_default();
-                    Err(format!("HTTP {}: {}", status, error_text))
-                }
-            }
-            Err(e) => {
-                if e.is_connect() {
-                    Err("Cannot connect to Ollama server. Please run 'ollama run gemma3:4b' in a terminal.".to_string())
-                } else if e.is_timeout() {
-                    Err("Request timed out. The model might be loading or the prompt is too complex.".to_string())
-                } else {
-                    Err(format!("Request failed: {}", e))
-                }
-            }
+        let response = self.client.post(&url).json(&embed_request).send().await
+            .map_err(|e| self.describe_request_error(e))?;
+        if !response.status().is_success() {
+            return Err(http_error(response).await);
+        }
+
+        let embed_response = response.json::<EmbedResponse>().await
+            .map_err(|e| format!("Failed to parse embeddings response: {}", e))?;
+        if embed_response.embeddings.len() != expected {
+            return Err(format!("Expected {} embeddings but got {}", expected, embed_response.embeddings.len()));
         }
+        Ok(embed_response.embeddings)
     }
 
-    pub async fn send_prompt_with_image(&self, prompt: &str, image_base64: &str, model: &str) -> Result<String, String> {
-        let chat_request = ChatRequest {
+    pub async fn show_model(&self, model: &str) -> Result<ModelInfo, String> {
+        let url = format!("{}/api/show", self.base_url);
+        let show_request = ShowRequest {
             model: model.to_string(),
-            messages: vec![ChatMessage {
-                role: "user".to_string(),
-                content: prompt.to_string(),
-                images: Some(vec![image_base64.to_string()]),
-            }],
-            stream: false,
         };
 
-        let url = format!("{}/api/chat", self.base_url);
-        
-        match self.client
-            .post(&url)
-            .json(&chat_request)
-            .send()
-            .await
-        {
-            Ok(response) => {
-                if response.status().is_success() {
-                    match response.json::<ChatResponse>().await {
-                        Ok(chat_response) => Ok(chat_response.message.content),
-                        Err(e) => Err(format!("Failed to parse response: {}", e)),
-                    }
-                } else {
-                    let status = response.status();
-                    let error_text = response.text().await.unwrap_or_default();
-                    
-                    // Check if it's a vision-related error
-                    if error_text.contains("vision") || error_text.contains("image") || error_text.contains("multimodal") {
-                        Err("VISION_NOT_SUPPORTED".to_string())
-                    } else {
-                        Err(format!("HTTP {}: {}", status, error_text))
-                    }
-                }
-            }
-            Err(e) => {
-                if e.is_connect() {
-                    Err("Cannot connect to Ollama server. Please run 'ollama run gemma3:4b' in a terminal.".to_string())
-                } else if e.is_timeout() {
-                    Err("Request timed out. The model might be loading or the prompt is too complex.".to_string())
-                } else {
-                    Err(format!("Request failed: {}", e))
-                }
-            }
+        let response = self.client.post(&url).json(&show_request).send().await
+            .map_err(|e| self.describe_request_error(e))?;
+        if !response.status().is_success() {
+            return Err(http_error(response).await);
         }
+
+        response.json::<ModelInfo>().await
+            .map_err(|e| format!("Failed to parse model info: {}", e))
     }
 
-    pub async fn send_prompt_with_history(&self, messages: Vec<ChatMessage>, model: &str) -> Result<String, String> {
-        let chat_request = ChatRequest {
+    /// Whether `model`'s template opens a `<think>` block in the prompt, so
+    /// its replies may only close it. Looked up once per model; a model that
+    /// cannot be looked up is assumed not to.
+    async fn opens_thinking(&self, model: &str) -> bool {
+        if let Some(opened) = self.opened_thinking.lock().ok().and_then(|cache| cache.get(model).copied()) {
+            return opened;
+        }
+        let Ok(info) = self.show_model(model).await else {
+            return false;
+        };
+        let opened = reasoning::template_opens_block(&info.template);
+        if let Ok(mut cache) = self.opened_thinking.lock() {
+            cache.insert(model.to_string(), opened);
+        }
+        opened
+    }
+
+    pub async fn copy_model(&self, source: &str, destination: &str) -> Result<(), String> {
+        let url = format!("{}/api/copy", self.base_url);
+        let copy_request = CopyRequest {
+            source: source.to_string(),
+            destination: destination.to_string(),
+        };
+
+        let response = self.client.post(&url).json(&copy_request).send().await
+            .map_err(|e| self.describe_request_error(e))?;
+        if !response.status().is_success() {
+            return Err(http_error(response).await);
+        }
+        Ok(())
+    }
+
+    pub async fn delete_model(&self, model: &str) -> Result<(), String> {
+        let url = format!("{}/api/delete", self.base_url);
+        let delete_request = DeleteRequest {
             model: model.to_string(),
-            messages,
-            stream: false,
         };
 
-        let url = format!("{}/api/chat", self.base_url);
-        
-        match self.client
-            .post(&url)
-            .json(&chat_request)
-            .send()
-            .await
-        {
-            Ok(response) => {
-                if response.status().is_success() {
-                    match response.json::<ChatResponse>().await {
-                        Ok(chat_response) => Ok(chat_response.message.content),
-                        Err(e) => Err(format!("Failed to parse response: {}", e)),
-                    }
-                } else {
-                    let status = response.status();
-                    let error_text = response.text().await.unwrap_or_default();
-                    
-                    // Check if it's a vision-related error
-                    if error_text.contains("vision") || error_text.contains("image") || error_text.contains("multimodal") {
-                        Err("VISION_NOT_SUPPORTED".to_string())
-                    } else {
-                        Err(format!("HTTP {}: {}", status, error_text))
-                    }
-                }
-            }
-            Err(e) => {
-                if e.is_connect() {
-                    Err("Cannot connect to Ollama server. Please run 'ollama run gemma3:4b' in a terminal.".to_string())
-                } else if e.is_timeout() {
-                    Err("Request timed out. The model might be loading or the prompt is too complex.".to_string())
-                } else {
-                    Err(format!("Request failed: {}", e))
-                }
-            }
+        let response = self.client.delete(&url).json(&delete_request).send().await
+            .map_err(|e| self.describe_request_error(e))?;
+        if !response.status().is_success() {
+            return Err(http_error(response).await);
+        }
+        Ok(())
+    }
+
+    pub async fn list_running_models(&self) -> Result<Vec<RunningModel>, String> {
+        let url = format!("{}/api/ps", self.base_url);
+
+        let response = self.client.get(&url).send().await
+            .map_err(|e| self.describe_request_error(e))?;
+        if !response.status().is_success() {
+            return Err(http_error(response).await);
+        }
+
+        response.json::<RunningModelsResponse>().await
+            .map(|running| running.models)
+            .map_err(|e| format!("Failed to parse running models response: {}", e))
+    }
+
+    fn describe_request_error(&self, e: reqwest::Error) -> String {
+        if e.is_connect() {
+            format!("Cannot connect to Ollama at {}. Please make sure the server is running.", self.base_url)
+        } else if e.is_timeout() {
+            "Request timed out. The model might be loading or the prompt is too complex.".to_string()
+        } else {
+            format!("Request failed: {}", e)
+        }
+    }
+}
+
+impl ChatProvider for OllamaClient {
+    async fn check_connection(&self) -> bool {
+        match self.client.get(&self.base_url).send().await {
+            Ok(response) => response.status().is_success(),
+            Err(_) => false,
         }
     }
 
-    pub async fn list_models(&self) -> Result<Vec<OllamaModel>, String> {
+    async fn list_models(&self) -> Result<Vec<OllamaModel>, String> {
         let url = format!("{}/api/tags", self.base_url);
         
         match self.client.get(&url).send().await {
@@ -223,4 +536,123 @@ impl OllamaClient {
             }
         }
     }
-}
\ No newline at end of file
+
+    async fn chat(
+        &self,
+        messages: Vec<ChatMessage>,
+        model: &str,
+        options: Option<GenerationOptions>,
+        tools: Vec<ToolDefinition>,
+        format: Option<serde_json::Value>,
+    ) -> Result<ChatResponse, String> {
+        let chat_request = ChatRequest::new(model, messages, false, options, tools, format);
+
+        let url = format!("{}/api/chat", self.base_url);
+
+        let response = self.client.post(&url).json(&chat_request).send().await
+            .map_err(|e| self.describe_request_error(e))?;
+        if !response.status().is_success() {
+            return Err(chat_http_error(response).await);
+        }
+
+        let mut response = response.json::<ChatResponse>().await
+            .map_err(|e| format!("Failed to parse response: {}", e))?;
+        let opened = self.opens_thinking(model).await;
+        reasoning::separate(&mut response.message, opened);
+        Ok(response)
+    }
+
+    async fn stream_chat<F>(&self, messages: Vec<ChatMessage>, model: &str, options: Option<GenerationOptions>, mut on_delta: F) -> Result<ChatResponse, String>
+    where
+        F: FnMut(ReplyPart, &str) + Send,
+    {
+        let chat_request = ChatRequest::new(model, messages, true, options, Vec::new(), None);
+
+        let url = format!("{}/api/chat", self.base_url);
+
+        let response = match self.client.post(&url).json(&chat_request).send().await {
+            Ok(response) => response,
+            Err(e) => return Err(self.describe_request_error(e)),
+        };
+
+        if !response.status().is_success() {
+            return Err(chat_http_error(response).await);
+        }
+
+        let mut reply = StreamedReply::new(self.opens_thinking(model).await);
+        let mut final_chunk = None;
+
+        read_ndjson(response, |chunk: ChatResponse| {
+            if let Some(thinking) = &chunk.message.thinking {
+                reply.push_thinking(thinking, &mut on_delta);
+            }
+            reply.push_content(&chunk.message.content, &mut on_delta);
+            if chunk.done {
+                final_chunk = Some(chunk);
+            }
+            final_chunk.is_some()
+        }, |e| self.describe_request_error(e)).await?;
+
+        match final_chunk {
+            Some(mut done) => {
+                reply.finish(&mut done.message, &mut on_delta);
+                Ok(done)
+            }
+            None => Err("Stream ended before the model finished responding.".to_string()),
+        }
+    }
+}
+
+/// Reads a newline-delimited JSON body, handing each object to `on_item` until
+/// it returns `true` or the body ends. An `{"error": ...}` line aborts the read.
+async fn read_ndjson<T, F, E>(response: reqwest::Response, mut on_item: F, describe_error: E) -> Result<(), String>
+where
+    T: DeserializeOwned,
+    F: FnMut(T) -> bool,
+    E: Fn(reqwest::Error) -> String,
+{
+    let mut stream = response.bytes_stream();
+    let mut buffer: Vec<u8> = Vec::new();
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(&describe_error)?;
+        buffer.extend_from_slice(&chunk);
+
+        // A network chunk may hold several lines or end partway through one
+        while let Some(newline) = buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=newline).collect();
+            if let Some(item) = parse_ndjson_line(&line)? {
+                if on_item(item) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    if let Some(item) = parse_ndjson_line(&buffer)? {
+        on_item(item);
+    }
+    Ok(())
+}
+
+fn parse_ndjson_line<T: DeserializeOwned>(line: &[u8]) -> Result<Option<T>, String> {
+    let line = line.trim_ascii();
+    if line.is_empty() {
+        return Ok(None);
+    }
+
+    if let Ok(stream_error) = serde_json::from_slice::<StreamError>(line) {
+        return Err(format!("Ollama error: {}", stream_error.error));
+    }
+
+    serde_json::from_slice::<T>(line)
+        .map(Some)
+        .map_err(|e| format!("Failed to parse stream chunk: {}", e))
+}
+
+async fn http_error(response: reqwest::Response) -> String {
+    let status = response.status();
+    let error_text = response.text().await.unwrap_or_default();
+    format!("HTTP {}: {}", status, error_text)
+}
+
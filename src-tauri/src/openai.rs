@@ -0,0 +1,528 @@
+use crate::ollama::{ChatMessage, ChatResponse, GenerationOptions, ModelDetails, OllamaModel, ReplyMetrics, ToolCall, ToolCallFunction, ToolDefinition};
+use crate::provider::{chat_http_error, ChatProvider};
+use crate::reasoning::{self, ReplyPart, StreamedReply};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Client for servers speaking the OpenAI chat completions protocol, such as
+/// llama.cpp server, vLLM and LM Studio. `base_url` includes the version
+/// prefix, e.g. `http://localhost:8080/v1`.
+#[derive(Clone)]
+pub struct OpenAiClient {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+#[derive(Debug, Serialize)]
+struct CompletionRequest {
+    model: String,
+    messages: Vec<OpenAiMessage>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    // Not part of OpenAI's API, but honoured by llama.cpp, vLLM and LM Studio
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<ToolDefinition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<Value>,
+    /// Asks for a final chunk carrying token usage when streaming.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+struct OpenAiMessage {
+    role: String,
+    /// A string, or text and image parts when the message carries images.
+    content: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_calls: Option<Vec<OpenAiToolCall>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAiToolCall {
+    #[serde(default)]
+    id: String,
+    #[serde(rename = "type", default = "function_type")]
+    kind: String,
+    function: OpenAiFunctionCall,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAiFunctionCall {
+    name: String,
+    /// JSON-encoded arguments.
+    #[serde(default)]
+    arguments: String,
+}
+
+fn function_type() -> String {
+    "function".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+struct CompletionResponse {
+    #[serde(default)]
+    model: String,
+    #[serde(default)]
+    created: i64,
+    choices: Vec<CompletionChoice>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Usage {
+    #[serde(default)]
+    prompt_tokens: Option<i64>,
+    #[serde(default)]
+    completion_tokens: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompletionChoice {
+    message: ResponseMessage,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseMessage {
+    #[serde(default)]
+    content: Option<String>,
+    // `reasoning_content` from DeepSeek and vLLM, `reasoning` from Ollama and OpenRouter
+    #[serde(default, alias = "reasoning")]
+    reasoning_content: Option<String>,
+    #[serde(default)]
+    tool_calls: Option<Vec<OpenAiToolCall>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompletionChunk {
+    #[serde(default)]
+    model: String,
+    #[serde(default)]
+    created: i64,
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChunkChoice {
+    #[serde(default)]
+    delta: ChunkDelta,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ChunkDelta {
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default, alias = "reasoning")]
+    reasoning_content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelList {
+    data: Vec<ModelEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelEntry {
+    id: String,
+    #[serde(default)]
+    created: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct EmbeddingRequest {
+    model: String,
+    input: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingEntry {
+    #[serde(default)]
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+impl OpenAiClient {
+    pub fn new(base_url: &str, client: reqwest::Client) -> Self {
+        Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Embeds each input with `model`, returning one vector per input in order.
+    pub async fn embed(&self, model: &str, input: Vec<String>) -> Result<Vec<Vec<f32>>, String> {
+        let url = format!("{}/embeddings", self.base_url);
+        let expected = input.len();
+        let embedding_request = EmbeddingRequest {
+            model: model.to_string(),
+            input,
+        };
+
+        let response = self.client.post(&url).json(&embedding_request).send().await
+            .map_err(|e| self.describe_request_error(e))?;
+        if !response.status().is_success() {
+            return Err(http_error(response).await);
+        }
+
+        let mut embedding_response = response.json::<EmbeddingResponse>().await
+            .map_err(|e| format!("Failed to parse embeddings response: {}", e))?;
+        if embedding_response.data.len() != expected {
+            return Err(format!("Expected {} embeddings but got {}", expected, embedding_response.data.len()));
+        }
+        embedding_response.data.sort_by_key(|entry| entry.index);
+        Ok(embedding_response.data.into_iter().map(|entry| entry.embedding).collect())
+    }
+
+    fn completion_request(
+        &self,
+        messages: Vec<ChatMessage>,
+        model: &str,
+        options: Option<GenerationOptions>,
+        tools: Vec<ToolDefinition>,
+        format: Option<Value>,
+        stream: bool,
+    ) -> CompletionRequest {
+        // num_ctx and repeat_penalty are server settings here, not request fields
+        let options = options.unwrap_or_default();
+        CompletionRequest {
+            model: model.to_string(),
+            messages: to_openai_messages(messages),
+            stream,
+            temperature: options.temperature,
+            top_p: options.top_p,
+            top_k: options.top_k,
+            max_tokens: options.num_predict.and_then(|limit| u32::try_from(limit).ok()).filter(|limit| *limit > 0),
+            seed: options.seed,
+            stop: options.stop,
+            tools,
+            response_format: format.map(|format| match format {
+                Value::String(_) => json!({ "type": "json_object" }),
+                schema => json!({ "type": "json_schema", "json_schema": { "name": "response", "schema": schema } }),
+            }),
+            stream_options: stream.then(|| json!({ "include_usage": true })),
+        }
+    }
+
+    fn describe_request_error(&self, e: reqwest::Error) -> String {
+        if e.is_connect() {
+            format!("Cannot connect to {}. Please make sure the server is running.", self.base_url)
+        } else if e.is_timeout() {
+            "Request timed out. The model might be loading or the prompt is too complex.".to_string()
+        } else {
+            format!("Request failed: {}", e)
+        }
+    }
+}
+
+impl ChatProvider for OpenAiClient {
+    async fn check_connection(&self) -> bool {
+        match self.client.get(format!("{}/models", self.base_url)).send().await {
+            Ok(response) => response.status().is_success(),
+            Err(_) => false,
+        }
+    }
+
+    async fn list_models(&self) -> Result<Vec<OllamaModel>, String> {
+        let url = format!("{}/models", self.base_url);
+
+        let response = self.client.get(&url).send().await
+            .map_err(|e| self.describe_request_error(e))?;
+        if !response.status().is_success() {
+            return Err(http_error(response).await);
+        }
+
+        let models = response.json::<ModelList>().await
+            .map_err(|e| format!("Failed to parse models response: {}", e))?;
+        // Only the name is known; size and digest are Ollama concepts
+        Ok(models.data.into_iter()
+            .map(|model| OllamaModel {
+                name: model.id,
+                size: 0,
+                digest: String::new(),
+                modified_at: timestamp(model.created),
+                details: ModelDetails::default(),
+            })
+            .collect())
+    }
+
+    async fn chat(
+        &self,
+        messages: Vec<ChatMessage>,
+        model: &str,
+        options: Option<GenerationOptions>,
+        tools: Vec<ToolDefinition>,
+        format: Option<Value>,
+    ) -> Result<ChatResponse, String> {
+        let completion_request = self.completion_request(messages, model, options, tools, format, false);
+        let url = format!("{}/chat/completions", self.base_url);
+        let started = Instant::now();
+
+        let response = self.client.post(&url).json(&completion_request).send().await
+            .map_err(|e| self.describe_request_error(e))?;
+        if !response.status().is_success() {
+            return Err(chat_http_error(response).await);
+        }
+
+        let completion = response.json::<CompletionResponse>().await
+            .map_err(|e| format!("Failed to parse response: {}", e))?;
+        let choice = completion.choices.into_iter().next()
+            .ok_or("The server returned no choices.")?;
+
+        let mut message = ChatMessage {
+            role: "assistant".to_string(),
+            content: choice.message.content.unwrap_or_default(),
+            tool_calls: choice.message.tool_calls.map(|calls| calls.into_iter().map(from_openai_tool_call).collect()),
+            thinking: choice.message.reasoning_content,
+            ..Default::default()
+        };
+        // Templates are not exposed here, so only a leading block is split off
+        reasoning::separate(&mut message, false);
+
+        Ok(ChatResponse {
+            model: if completion.model.is_empty() { model.to_string() } else { completion.model },
+            created_at: timestamp(completion.created),
+            message,
+            done: true,
+            done_reason: choice.finish_reason,
+            metrics: reply_metrics(completion.usage, started.elapsed(), None),
+        })
+    }
+
+    async fn stream_chat<F>(
+        &self,
+        messages: Vec<ChatMessage>,
+        model: &str,
+        options: Option<GenerationOptions>,
+        mut on_delta: F,
+    ) -> Result<ChatResponse, String>
+    where
+        F: FnMut(ReplyPart, &str) + Send,
+    {
+        let completion_request = self.completion_request(messages, model, options, Vec::new(), None, true);
+        let url = format!("{}/chat/completions", self.base_url);
+        let started = Instant::now();
+
+        let response = self.client.post(&url).json(&completion_request).send().await
+            .map_err(|e| self.describe_request_error(e))?;
+        if !response.status().is_success() {
+            return Err(chat_http_error(response).await);
+        }
+
+        let mut reply = StreamedReply::new(false);
+        let mut response_model = model.to_string();
+        let mut created = 0;
+        let mut finish_reason = None;
+        let mut usage = None;
+        let mut first_token = None;
+
+        let finished = read_sse(response, |chunk: CompletionChunk| {
+            if !chunk.model.is_empty() {
+                response_model = chunk.model;
+            }
+            if chunk.created > 0 {
+                created = chunk.created;
+            }
+            if chunk.usage.is_some() {
+                usage = chunk.usage;
+            }
+            for choice in chunk.choices {
+                if let Some(delta) = choice.delta.reasoning_content.filter(|delta| !delta.is_empty()) {
+                    first_token.get_or_insert_with(Instant::now);
+                    reply.push_thinking(&delta, &mut on_delta);
+                }
+                if let Some(delta) = choice.delta.content.filter(|delta| !delta.is_empty()) {
+                    first_token.get_or_insert_with(Instant::now);
+                    reply.push_content(&delta, &mut on_delta);
+                }
+                if choice.finish_reason.is_some() {
+                    finish_reason = choice.finish_reason;
+                }
+            }
+        }, |e| self.describe_request_error(e)).await?;
+
+        if !finished && finish_reason.is_none() {
+            return Err("Stream ended before the model finished responding.".to_string());
+        }
+        let mut message = ChatMessage {
+            role: "assistant".to_string(),
+            ..Default::default()
+        };
+        reply.finish(&mut message, &mut on_delta);
+
+        Ok(ChatResponse {
+            model: response_model,
+            created_at: timestamp(created),
+            message,
+            done: true,
+            done_reason: finish_reason,
+            metrics: reply_metrics(usage, started.elapsed(), first_token.map(|first: Instant| first.elapsed())),
+        })
+    }
+}
+
+/// Converts our history to OpenAI messages. Tool calls carry no ids in our
+/// history, so ids are made up here and handed to the matching tool results.
+fn to_openai_messages(messages: Vec<ChatMessage>) -> Vec<OpenAiMessage> {
+    let mut pending_calls: VecDeque<(String, String)> = VecDeque::new();
+    messages.into_iter().enumerate().map(|(index, message)| {
+        let tool_calls = message.tool_calls.map(|calls| {
+            calls.into_iter().enumerate().map(|(call_index, call)| {
+                let id = format!("call_{}_{}", index, call_index);
+                pending_calls.push_back((call.function.name.clone(), id.clone()));
+                OpenAiToolCall {
+                    id,
+                    kind: function_type(),
+                    function: OpenAiFunctionCall {
+                        name: call.function.name,
+                        arguments: match call.function.arguments {
+                            Value::Null => "{}".to_string(),
+                            arguments => arguments.to_string(),
+                        },
+                    },
+                }
+            }).collect()
+        });
+
+        let tool_call_id = if message.role == "tool" {
+            let position = pending_calls.iter()
+                .position(|(name, _)| Some(name) == message.tool_name.as_ref())
+                .unwrap_or(0);
+            pending_calls.remove(position).map(|(_, id)| id)
+        } else {
+            None
+        };
+
+        let content = match message.images.filter(|images| !images.is_empty()) {
+            Some(images) => {
+                let mut parts = vec![json!({ "type": "text", "text": message.content })];
+                parts.extend(images.iter().map(|image| json!({
+                    "type": "image_url",
+                    "image_url": { "url": format!("data:{};base64,{}", image_mime_type(image), image) },
+                })));
+                Value::Array(parts)
+            }
+            None => Value::String(message.content),
+        };
+
+        OpenAiMessage { role: message.role, content, tool_calls, tool_call_id }
+    }).collect()
+}
+
+fn from_openai_tool_call(call: OpenAiToolCall) -> ToolCall {
+    ToolCall {
+        function: ToolCallFunction {
+            arguments: serde_json::from_str(&call.function.arguments)
+                .unwrap_or(Value::String(call.function.arguments)),
+            name: call.function.name,
+        },
+    }
+}
+
+// Recognises the image type from the start of its base64 encoding
+fn image_mime_type(image_base64: &str) -> &'static str {
+    if image_base64.starts_with("iVBOR") {
+        "image/png"
+    } else if image_base64.starts_with("R0lGOD") {
+        "image/gif"
+    } else if image_base64.starts_with("UklGR") {
+        "image/webp"
+    } else {
+        "image/jpeg"
+    }
+}
+
+/// The server reports only token counts, so durations are timed here:
+/// the whole request, and when streaming, generation from the first token.
+fn reply_metrics(usage: Option<Usage>, total: Duration, generating: Option<Duration>) -> ReplyMetrics {
+    let (prompt_eval_count, eval_count) = usage
+        .map(|usage| (usage.prompt_tokens, usage.completion_tokens))
+        .unwrap_or_default();
+    ReplyMetrics {
+        total_duration: i64::try_from(total.as_nanos()).ok(),
+        prompt_eval_count,
+        eval_count,
+        eval_duration: generating.and_then(|duration| i64::try_from(duration.as_nanos()).ok()),
+        ..Default::default()
+    }
+}
+
+fn timestamp(created: i64) -> String {
+    chrono::DateTime::from_timestamp(created, 0)
+        .filter(|_| created > 0)
+        .map(|time| time.to_rfc3339())
+        .unwrap_or_default()
+}
+
+/// Reads a server-sent events body, handing each `data:` payload to
+/// `on_item`. Returns `true` if the stream was closed with `[DONE]`; an
+/// `{"error": ...}` payload aborts the read.
+async fn read_sse<T, F, E>(response: reqwest::Response, mut on_item: F, describe_error: E) -> Result<bool, String>
+where
+    T: serde::de::DeserializeOwned,
+    F: FnMut(T),
+    E: Fn(reqwest::Error) -> String,
+{
+    let mut stream = response.bytes_stream();
+    let mut buffer: Vec<u8> = Vec::new();
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(&describe_error)?;
+        buffer.extend_from_slice(&chunk);
+
+        while let Some(newline) = buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=newline).collect();
+            let line = String::from_utf8_lossy(&line);
+            let Some(data) = line.trim().strip_prefix("data:").map(str::trim) else {
+                continue;
+            };
+            if data == "[DONE]" {
+                return Ok(true);
+            }
+
+            let value: Value = serde_json::from_str(data)
+                .map_err(|e| format!("Failed to parse stream chunk: {}", e))?;
+            if let Some(error) = value.get("error") {
+                let message = error.get("message").and_then(Value::as_str).map(str::to_string)
+                    .unwrap_or_else(|| error.to_string());
+                return Err(format!("Server error: {}", message));
+            }
+            on_item(serde_json::from_value(value).map_err(|e| format!("Failed to parse stream chunk: {}", e))?);
+        }
+    }
+    Ok(false)
+}
+
+async fn http_error(response: reqwest::Response) -> String {
+    let status = response.status();
+    let error_text = response.text().await.unwrap_or_default();
+    format!("HTTP {}: {}", status, error_text)
+}
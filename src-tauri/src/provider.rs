@@ -0,0 +1,208 @@
+use crate::db::Endpoint;
+use crate::ollama::{ChatMessage, ChatResponse, GenerationOptions, OllamaClient, OllamaModel, ToolDefinition};
+use crate::openai::OpenAiClient;
+use crate::reasoning::ReplyPart;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// The protocol an endpoint speaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProviderKind {
+    #[serde(rename = "ollama")]
+    Ollama,
+    /// `/v1/chat/completions`, as served by llama.cpp, vLLM and LM Studio.
+    #[serde(rename = "openai")]
+    OpenAi,
+}
+
+impl ProviderKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProviderKind::Ollama => "ollama",
+            ProviderKind::OpenAi => "openai",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<ProviderKind> {
+        match value {
+            "ollama" => Some(ProviderKind::Ollama),
+            "openai" => Some(ProviderKind::OpenAi),
+            _ => None,
+        }
+    }
+}
+
+/// What the app needs from a model server: a health check, the models it
+/// offers, and chat with or without streaming.
+pub trait ChatProvider {
+    async fn check_connection(&self) -> bool;
+
+    async fn list_models(&self) -> Result<Vec<OllamaModel>, String>;
+
+    /// Sends a non-streaming chat request, optionally offering `tools` or
+    /// constraining the reply to a `format` schema, and returns the whole
+    /// response so the caller can inspect any `tool_calls`.
+    async fn chat(
+        &self,
+        messages: Vec<ChatMessage>,
+        model: &str,
+        options: Option<GenerationOptions>,
+        tools: Vec<ToolDefinition>,
+        format: Option<serde_json::Value>,
+    ) -> Result<ChatResponse, String>;
+
+    /// Streams a reply, calling `on_delta` for each fragment of the answer
+    /// or the model's reasoning as it arrives. Returns the final response
+    /// with both accumulated into `message.content` and `message.thinking`.
+    async fn stream_chat<F>(
+        &self,
+        messages: Vec<ChatMessage>,
+        model: &str,
+        options: Option<GenerationOptions>,
+        on_delta: F,
+    ) -> Result<ChatResponse, String>
+    where
+        F: FnMut(ReplyPart, &str) + Send;
+}
+
+/// Client for the selected endpoint, whichever protocol it speaks.
+#[derive(Clone)]
+pub enum Provider {
+    Ollama(OllamaClient),
+    OpenAi(OpenAiClient),
+}
+
+impl Provider {
+    /// Connects to `endpoint`, or to the local Ollama default when none is selected.
+    pub fn for_endpoint(endpoint: Option<&Endpoint>) -> Result<Provider, String> {
+        let Some(endpoint) = endpoint else {
+            return Ok(Provider::Ollama(OllamaClient::new(crate::db::DEFAULT_ENDPOINT_URL)));
+        };
+        let client = http_client(endpoint.api_key.as_deref(), &endpoint.headers)?;
+        Ok(match endpoint.provider {
+            ProviderKind::Ollama => Provider::Ollama(OllamaClient::with_http_client(&endpoint.base_url, client)),
+            ProviderKind::OpenAi => Provider::OpenAi(OpenAiClient::new(&endpoint.base_url, client)),
+        })
+    }
+
+    pub fn kind(&self) -> ProviderKind {
+        match self {
+            Provider::Ollama(_) => ProviderKind::Ollama,
+            Provider::OpenAi(_) => ProviderKind::OpenAi,
+        }
+    }
+
+    /// The Ollama client, for model management that only Ollama offers.
+    pub fn ollama(&self) -> Result<&OllamaClient, String> {
+        match self {
+            Provider::Ollama(client) => Ok(client),
+            Provider::OpenAi(_) => Err("This is only available for Ollama endpoints.".to_string()),
+        }
+    }
+
+    /// Digest of the installed `model`, for recording which build answered.
+    /// `None` when the server does not report digests or the lookup fails.
+    pub async fn model_digest(&self, model: &str) -> Option<String> {
+        self.list_models().await.ok()?
+            .into_iter()
+            // Ollama lists untagged names with their implied `:latest`
+            .find(|installed| installed.name == model || installed.name.strip_suffix(":latest") == Some(model))
+            .map(|installed| installed.digest)
+            .filter(|digest| !digest.is_empty())
+    }
+
+    /// Embeds each input with `model`, returning one vector per input in order.
+    pub async fn embed(&self, model: &str, input: Vec<String>) -> Result<Vec<Vec<f32>>, String> {
+        match self {
+            Provider::Ollama(client) => client.embed(model, input).await,
+            Provider::OpenAi(client) => client.embed(model, input).await,
+        }
+    }
+}
+
+impl ChatProvider for Provider {
+    async fn check_connection(&self) -> bool {
+        match self {
+            Provider::Ollama(client) => client.check_connection().await,
+            Provider::OpenAi(client) => client.check_connection().await,
+        }
+    }
+
+    async fn list_models(&self) -> Result<Vec<OllamaModel>, String> {
+        match self {
+            Provider::Ollama(client) => client.list_models().await,
+            Provider::OpenAi(client) => client.list_models().await,
+        }
+    }
+
+    async fn chat(
+        &self,
+        messages: Vec<ChatMessage>,
+        model: &str,
+        options: Option<GenerationOptions>,
+        tools: Vec<ToolDefinition>,
+        format: Option<serde_json::Value>,
+    ) -> Result<ChatResponse, String> {
+        match self {
+            Provider::Ollama(client) => client.chat(messages, model, options, tools, format).await,
+            Provider::OpenAi(client) => client.chat(messages, model, options, tools, format).await,
+        }
+    }
+
+    async fn stream_chat<F>(
+        &self,
+        messages: Vec<ChatMessage>,
+        model: &str,
+        options: Option<GenerationOptions>,
+        on_delta: F,
+    ) -> Result<ChatResponse, String>
+    where
+        F: FnMut(ReplyPart, &str) + Send,
+    {
+        match self {
+            Provider::Ollama(client) => client.stream_chat(messages, model, options, on_delta).await,
+            Provider::OpenAi(client) => client.stream_chat(messages, model, options, on_delta).await,
+        }
+    }
+}
+
+/// Builds the HTTP client for an endpoint, sending `api_key` as a bearer
+/// token and `headers` with every request.
+pub fn http_client(api_key: Option<&str>, headers: &BTreeMap<String, String>) -> Result<reqwest::Client, String> {
+    let mut default_headers = HeaderMap::new();
+    if let Some(api_key) = api_key.filter(|key| !key.is_empty()) {
+        let value = HeaderValue::from_str(&format!("Bearer {}", api_key))
+            .map_err(|_| "API key contains characters that cannot be sent in a header".to_string())?;
+        default_headers.insert(AUTHORIZATION, value);
+    }
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.trim().as_bytes())
+            .map_err(|_| format!("Invalid header name: {}", name))?;
+        let value = HeaderValue::from_str(value.trim())
+            .map_err(|_| format!("Invalid value for header {}", name))?;
+        default_headers.insert(name, value);
+    }
+
+    reqwest::Client::builder()
+        .default_headers(default_headers)
+        // Idle timeout rather than a total one, so long streamed replies aren't cut off
+        .read_timeout(Duration::from_secs(120))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+/// Turns a failed chat response into an error, reporting models that cannot
+/// take images as `VISION_NOT_SUPPORTED` for the frontend.
+pub async fn chat_http_error(response: reqwest::Response) -> String {
+    let status = response.status();
+    let error_text = response.text().await.unwrap_or_default();
+
+    // Check if it's a vision-related error
+    if error_text.contains("vision") || error_text.contains("image") || error_text.contains("multimodal") {
+        "VISION_NOT_SUPPORTED".to_string()
+    } else {
+        format!("HTTP {}: {}", status, error_text)
+    }
+}
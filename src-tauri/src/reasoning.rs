@@ -0,0 +1,313 @@
+use crate::ollama::ChatMessage;
+
+const OPEN_TAG: &str = "<think>";
+const CLOSE_TAG: &str = "</think>";
+
+/// Which part of a reply a streamed fragment belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyPart {
+    Content,
+    Thinking,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    // Nothing but whitespace yet; a `<think>` here opens a reasoning block
+    Start,
+    // Like `Start`, for a prompt that opened the block; anything but a
+    // repeated `<think>` is already reasoning
+    OpenedStart,
+    Thinking,
+    // Just past `</think>`; the whitespace separating the answer is dropped
+    AfterThinking,
+    Content,
+}
+
+/// Moves `<think>…</think>` blocks out of streamed content, for models that
+/// inline their reasoning instead of using Ollama's `thinking` field. Only a
+/// block opening the reply counts; tags later on are the model talking
+/// about them. Tags split across fragments are held back until complete.
+struct InlineThinking {
+    state: State,
+    opened: bool,
+    pending: String,
+}
+
+impl InlineThinking {
+    /// `opened` says the prompt opened a `<think>` block for the reply.
+    fn new(opened: bool) -> Self {
+        let state = if opened { State::OpenedStart } else { State::Start };
+        InlineThinking { state, opened, pending: String::new() }
+    }
+
+    /// Whether the prompt opened a block the reply has not closed yet.
+    fn in_opened_block(&self) -> bool {
+        self.opened && matches!(self.state, State::OpenedStart | State::Thinking)
+    }
+
+    /// Treats the reply as plain once the server reports reasoning in its own
+    /// field, which means it parsed the prompt's block out of the content.
+    fn drop_opened_block(&mut self) {
+        if self.state == State::OpenedStart {
+            self.state = State::Start;
+            self.opened = false;
+        }
+    }
+
+    /// Feeds the next fragment, returning what can be shown so far.
+    fn push(&mut self, fragment: &str) -> Vec<(ReplyPart, String)> {
+        self.pending.push_str(fragment);
+        let mut parts = Vec::new();
+
+        loop {
+            match self.state {
+                State::Start | State::OpenedStart => {
+                    let trimmed = self.pending.trim_start();
+                    if let Some(rest) = trimmed.strip_prefix(OPEN_TAG) {
+                        self.pending = rest.to_string();
+                        self.state = State::Thinking;
+                    } else if OPEN_TAG.starts_with(trimmed) {
+                        return parts;
+                    } else if self.state == State::OpenedStart {
+                        self.state = State::Thinking;
+                    } else {
+                        self.state = State::Content;
+                    }
+                }
+                State::Thinking => match self.pending.find(CLOSE_TAG) {
+                    Some(end) => {
+                        push_part(&mut parts, ReplyPart::Thinking, &self.pending[..end]);
+                        self.pending.drain(..end + CLOSE_TAG.len());
+                        self.state = State::AfterThinking;
+                    }
+                    None => {
+                        let keep = partial_tag_len(&self.pending, CLOSE_TAG);
+                        let ready: String = self.pending.drain(..self.pending.len() - keep).collect();
+                        push_part(&mut parts, ReplyPart::Thinking, &ready);
+                        return parts;
+                    }
+                },
+                State::AfterThinking => {
+                    let trimmed = self.pending.trim_start();
+                    if trimmed.is_empty() {
+                        self.pending.clear();
+                        return parts;
+                    }
+                    self.pending = trimmed.to_string();
+                    self.state = State::Content;
+                }
+                State::Content => {
+                    push_part(&mut parts, ReplyPart::Content, &self.pending);
+                    self.pending.clear();
+                    return parts;
+                }
+            }
+        }
+    }
+
+    /// Flushes what was held back once the reply has ended. An unclosed
+    /// block, as left by a cancelled reply, is all reasoning.
+    fn finish(&mut self) -> Vec<(ReplyPart, String)> {
+        let part = match self.state {
+            State::OpenedStart | State::Thinking => ReplyPart::Thinking,
+            _ => ReplyPart::Content,
+        };
+        let mut parts = Vec::new();
+        push_part(&mut parts, part, &std::mem::take(&mut self.pending));
+        self.state = State::Content;
+        parts
+    }
+}
+
+fn push_part(parts: &mut Vec<(ReplyPart, String)>, part: ReplyPart, text: &str) {
+    if !text.is_empty() {
+        parts.push((part, text.to_string()));
+    }
+}
+
+// Length of the longest suffix of `text` that could be the start of `tag`
+fn partial_tag_len(text: &str, tag: &str) -> usize {
+    (1..tag.len().min(text.len() + 1))
+        .rev()
+        .find(|len| text.is_char_boundary(text.len() - len) && tag.starts_with(&text[text.len() - len..]))
+        .unwrap_or(0)
+}
+
+/// Whether a chat template ends the prompt inside an open `<think>` block,
+/// so the model's reply starts with its reasoning and only closes the tag.
+pub fn template_opens_block(template: &str) -> bool {
+    match (template.rfind(OPEN_TAG), template.rfind(CLOSE_TAG)) {
+        (Some(open), Some(close)) => open > close,
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
+}
+
+/// Splits a whole reply into its answer and a leading `<think>` block.
+pub fn split(content: &str) -> (String, Option<String>) {
+    split_from(InlineThinking::new(false), content)
+}
+
+/// Like `split`, for a reply to a prompt that opened the block itself (see
+/// `template_opens_block`): without a leading block, everything before the
+/// first `</think>` is reasoning, and a reply that never closes it is all
+/// answer.
+pub fn split_opened(content: &str) -> (String, Option<String>) {
+    split_from(InlineThinking::new(true), content)
+}
+
+fn split_from(inline: InlineThinking, content: &str) -> (String, Option<String>) {
+    let mut reply = StreamedReply { inline, content: String::new(), thinking: String::new() };
+    reply.push_content(content, &mut |_, _| {});
+    reply.flush(&mut |_, _| {});
+    (reply.content, Some(reply.thinking.trim().to_string()).filter(|thinking| !thinking.is_empty()))
+}
+
+/// Moves inline reasoning out of a whole reply's content, unless the
+/// server already reported it separately. `opened` says the prompt opened
+/// a `<think>` block for the reply.
+pub fn separate(message: &mut ChatMessage, opened: bool) {
+    match message.thinking.take().filter(|thinking| !thinking.trim().is_empty()) {
+        Some(thinking) => message.thinking = Some(thinking.trim().to_string()),
+        None if opened => (message.content, message.thinking) = split_opened(&message.content),
+        None => (message.content, message.thinking) = split(&message.content),
+    }
+}
+
+/// Accumulates a streamed reply, passing each fragment on as it arrives
+/// with inline reasoning told apart from the answer.
+pub struct StreamedReply {
+    inline: InlineThinking,
+    content: String,
+    thinking: String,
+}
+
+impl StreamedReply {
+    /// `opened` says the prompt opened a `<think>` block for the reply, so
+    /// its content is reasoning until the block closes.
+    pub fn new(opened: bool) -> Self {
+        StreamedReply { inline: InlineThinking::new(opened), content: String::new(), thinking: String::new() }
+    }
+
+    /// Adds reasoning the server reported in its own field.
+    pub fn push_thinking<F: FnMut(ReplyPart, &str)>(&mut self, fragment: &str, on_delta: &mut F) {
+        if !fragment.is_empty() {
+            self.inline.drop_opened_block();
+            self.thinking.push_str(fragment);
+            on_delta(ReplyPart::Thinking, fragment);
+        }
+    }
+
+    pub fn push_content<F: FnMut(ReplyPart, &str)>(&mut self, fragment: &str, on_delta: &mut F) {
+        let parts = self.inline.push(fragment);
+        self.forward(parts, on_delta);
+    }
+
+    /// Puts the accumulated reply into `message`, flushing anything held back.
+    pub fn finish<F: FnMut(ReplyPart, &str)>(mut self, message: &mut ChatMessage, on_delta: &mut F) {
+        self.flush(on_delta);
+        message.content = self.content;
+        message.thinking = Some(self.thinking.trim().to_string()).filter(|thinking| !thinking.is_empty());
+    }
+
+    fn flush<F: FnMut(ReplyPart, &str)>(&mut self, on_delta: &mut F) {
+        let unclosed = self.inline.in_opened_block();
+        let parts = self.inline.finish();
+        self.forward(parts, on_delta);
+        // A whole reply that never closed the prompt's block was all answer
+        if unclosed {
+            self.content = std::mem::take(&mut self.thinking);
+        }
+    }
+
+    fn forward<F: FnMut(ReplyPart, &str)>(&mut self, parts: Vec<(ReplyPart, String)>, on_delta: &mut F) {
+        for (part, text) in parts {
+            match part {
+                ReplyPart::Content => self.content.push_str(&text),
+                ReplyPart::Thinking => self.thinking.push_str(&text),
+            }
+            on_delta(part, &text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_a_leading_block() {
+        let (answer, thinking) = split("<think>\nAdd them.\n</think>\n\n4");
+        assert_eq!(answer, "4");
+        assert_eq!(thinking.as_deref(), Some("Add them."));
+    }
+
+    #[test]
+    fn keeps_a_mentioned_closing_tag_in_the_answer() {
+        let reply = "Models end their reasoning with </think> before answering.";
+        assert_eq!(split(reply), (reply.to_string(), None));
+    }
+
+    #[test]
+    fn splits_at_a_lone_closing_tag_when_the_prompt_opened_the_block() {
+        let (answer, thinking) = split_opened("Add them.\n</think>\n\n4");
+        assert_eq!(answer, "4");
+        assert_eq!(thinking.as_deref(), Some("Add them."));
+
+        let (answer, thinking) = split_opened("<think>Add them.</think>4 is </think>-free");
+        assert_eq!(answer, "4 is </think>-free");
+        assert_eq!(thinking.as_deref(), Some("Add them."));
+    }
+
+    fn stream(reply: &mut StreamedReply, fragments: &[&str]) -> Vec<(ReplyPart, String)> {
+        let mut deltas = Vec::new();
+        for fragment in fragments {
+            reply.push_content(fragment, &mut |part, text| deltas.push((part, text.to_string())));
+        }
+        deltas
+    }
+
+    #[test]
+    fn streams_reasoning_when_the_prompt_opened_the_block() {
+        let mut reply = StreamedReply::new(true);
+        let deltas = stream(&mut reply, &["Add ", "them.\n</th", "ink>\n\n", "4"]);
+        assert_eq!(deltas, vec![
+            (ReplyPart::Thinking, "Add ".to_string()),
+            (ReplyPart::Thinking, "them.\n".to_string()),
+            (ReplyPart::Content, "4".to_string()),
+        ]);
+
+        let mut message = ChatMessage { role: "assistant".to_string(), content: String::new(), images: None, tool_calls: None, tool_name: None, thinking: None };
+        reply.finish(&mut message, &mut |_, _| {});
+        assert_eq!(message.content, "4");
+        assert_eq!(message.thinking.as_deref(), Some("Add them."));
+    }
+
+    #[test]
+    fn streams_a_cancelled_reply_inside_an_opened_block_as_reasoning() {
+        let mut reply = StreamedReply::new(true);
+        let deltas = stream(&mut reply, &["Add ", "them"]);
+        assert!(deltas.iter().all(|(part, _)| *part == ReplyPart::Thinking));
+    }
+
+    #[test]
+    fn keeps_a_whole_reply_that_never_closed_the_opened_block_as_the_answer() {
+        assert_eq!(split_opened("4"), ("4".to_string(), None));
+    }
+
+    #[test]
+    fn leaves_content_alone_when_the_server_reports_reasoning() {
+        let mut reply = StreamedReply::new(true);
+        let mut deltas = Vec::new();
+        reply.push_thinking("Add them.", &mut |part, text| deltas.push((part, text.to_string())));
+        deltas.extend(stream(&mut reply, &["", "4"]));
+        assert_eq!(deltas, vec![(ReplyPart::Thinking, "Add them.".to_string()), (ReplyPart::Content, "4".to_string())]);
+    }
+
+    #[test]
+    fn recognizes_templates_that_open_the_block() {
+        assert!(template_opens_block("{{ .Prompt }}<|im_start|>assistant\n<think>\n"));
+        assert!(!template_opens_block("{{ if .Thinking }}<think>{{ .Thinking }}</think>{{ end }}{{ .Content }}"));
+        assert!(!template_opens_block("{{ .Prompt }}"));
+    }
+}
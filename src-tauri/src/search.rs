@@ -0,0 +1,115 @@
+// Markers passed to FTS5 `snippet()`; control characters never appear in
+// chat text, so they survive HTML escaping unambiguously.
+const HIGHLIGHT_START: char = '\u{1}';
+const HIGHLIGHT_END: char = '\u{2}';
+
+/// Turns user input into a safe FTS5 `MATCH` expression. Quoted text is kept
+/// as a phrase, a trailing `*` makes a prefix match, and everything else is
+/// matched as individual terms that must all appear. Returns `None` when the
+/// input has no searchable terms.
+pub fn build_match_query(input: &str) -> Option<String> {
+    let mut parts = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        if c == '"' {
+            let phrase: String = chars.by_ref().take_while(|c| *c != '"').collect();
+            if !phrase.trim().is_empty() {
+                parts.push(quote(phrase.trim()));
+            }
+            continue;
+        }
+
+        let mut term = String::from(c);
+        while let Some(next) = chars.peek() {
+            if next.is_whitespace() || *next == '"' {
+                break;
+            }
+            term.push(*next);
+            chars.next();
+        }
+
+        let prefix = term.ends_with('*');
+        let term = term.trim_end_matches('*');
+        if term.is_empty() {
+            continue;
+        }
+        parts.push(if prefix { format!("{}*", quote(term)) } else { quote(term) });
+    }
+
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join(" "))
+    }
+}
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+/// Upper-bounds an end date given without a time so the whole day is included.
+pub fn end_of_day(date: &str) -> String {
+    if date.len() == 10 {
+        format!("{} 23:59:59", date)
+    } else {
+        date.to_string()
+    }
+}
+
+/// HTML-escapes a snippet and converts the FTS5 markers into `<mark>` tags.
+pub fn highlight_snippet(snippet: &str) -> String {
+    let mut html = String::with_capacity(snippet.len());
+    for c in snippet.chars() {
+        match c {
+            HIGHLIGHT_START => html.push_str("<mark>"),
+            HIGHLIGHT_END => html.push_str("</mark>"),
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_every_term_and_phrase() {
+        assert_eq!(build_match_query("hello world").as_deref(), Some("\"hello\" \"world\""));
+        assert_eq!(build_match_query("\"exact phrase\" other").as_deref(), Some("\"exact phrase\" \"other\""));
+        assert_eq!(build_match_query("\"unterminated phrase").as_deref(), Some("\"unterminated phrase\""));
+        assert_eq!(build_match_query("conf*").as_deref(), Some("\"conf\"*"));
+    }
+
+    #[test]
+    fn neutralizes_fts5_syntax() {
+        assert_eq!(build_match_query("cats OR dogs NOT birds").as_deref(), Some("\"cats\" \"OR\" \"dogs\" \"NOT\" \"birds\""));
+        assert_eq!(build_match_query("title:secret NEAR(a b)").as_deref(), Some("\"title:secret\" \"NEAR(a\" \"b)\""));
+        assert_eq!(build_match_query("a\"b").as_deref(), Some("\"a\" \"b\""));
+    }
+
+    #[test]
+    fn has_no_query_without_terms() {
+        assert_eq!(build_match_query(""), None);
+        assert_eq!(build_match_query("  \t "), None);
+        assert_eq!(build_match_query("\"\" ** \"  \""), None);
+    }
+
+    #[test]
+    fn escapes_snippets_and_marks_matches() {
+        assert_eq!(
+            highlight_snippet("<b>a</b> & \u{1}match\u{2} \"q\" 'x'"),
+            "&lt;b&gt;a&lt;/b&gt; &amp; <mark>match</mark> &quot;q&quot; &#39;x&#39;",
+        );
+    }
+}
@@ -0,0 +1,56 @@
+use jsonschema::Validator;
+use serde_json::Value;
+
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+pub fn compile_schema(schema: &Value) -> Result<Validator, String> {
+    jsonschema::validator_for(schema).map_err(|e| format!("Invalid JSON Schema: {}", e))
+}
+
+/// Parses a model reply as JSON and checks it against the schema, returning
+/// every problem found so they can be sent back to the model.
+pub fn validate_reply(validator: &Validator, reply: &str) -> Result<Value, Vec<String>> {
+    let value: Value = serde_json::from_str(strip_code_fence(reply))
+        .map_err(|e| vec![format!("Reply is not valid JSON: {}", e)])?;
+
+    let errors: Vec<String> = validator.iter_errors(&value)
+        .map(|error| {
+            let path = error.instance_path.to_string();
+            if path.is_empty() {
+                error.to_string()
+            } else {
+                format!("{}: {}", path, error)
+            }
+        })
+        .collect();
+
+    if errors.is_empty() {
+        Ok(value)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Follow-up user turn asking the model to fix a reply that failed validation.
+pub fn correction_prompt(errors: &[String]) -> String {
+    let mut prompt = String::from("Your previous reply did not match the required JSON schema:\n");
+    for error in errors {
+        prompt.push_str("- ");
+        prompt.push_str(error);
+        prompt.push('\n');
+    }
+    prompt.push_str("Reply again with only JSON that satisfies the schema.");
+    prompt
+}
+
+// Some models wrap JSON in a markdown fence even when `format` is set
+fn strip_code_fence(reply: &str) -> &str {
+    let trimmed = reply.trim();
+    match trimmed.strip_prefix("```") {
+        Some(rest) => {
+            let rest = rest.strip_prefix("json").unwrap_or(rest);
+            rest.strip_suffix("```").unwrap_or(rest).trim()
+        }
+        None => trimmed,
+    }
+}
@@ -0,0 +1,88 @@
+use crate::db::Message;
+use crate::ollama::{ChatMessage, GenerationOptions};
+use serde::Serialize;
+
+/// App event emitted once a generated title has been stored.
+pub const TITLE_UPDATED_EVENT: &str = "conversation-title-updated";
+
+const MAX_TITLE_CHARS: usize = 60;
+// Only the start of each turn is sent; it is plenty to name the topic
+const MAX_EXCERPT_CHARS: usize = 1000;
+
+const TITLE_PROMPT: &str = "Write a short title of at most six words for the conversation below. \
+    Reply with the title only, without quotes or trailing punctuation.";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TitleUpdated {
+    pub conversation_id: i32,
+    pub title: String,
+}
+
+/// The first user turn and the first completed assistant reply, if the
+/// conversation has had its first exchange.
+pub fn first_exchange(messages: &[Message]) -> Option<(&str, &str)> {
+    let user = messages.iter()
+        .find(|message| message.role == "user" && !message.content.trim().is_empty())?;
+    let assistant = messages.iter()
+        .find(|message| {
+            message.role == "assistant"
+                && message.status == crate::db::MESSAGE_STATUS_COMPLETE
+                && !message.content.trim().is_empty()
+        })?;
+    Some((&user.content, &assistant.content))
+}
+
+pub fn title_request(user: &str, assistant: &str) -> Vec<ChatMessage> {
+    vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: TITLE_PROMPT.to_string(),
+            ..Default::default()
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: format!(
+                "User: {}\n\nAssistant: {}",
+                excerpt(user),
+                excerpt(assistant)
+            ),
+            ..Default::default()
+        },
+    ]
+}
+
+/// Keeps the reply short and deterministic enough for a title. Thinking is
+/// off so reasoning does not use up the token limit.
+pub fn title_options() -> GenerationOptions {
+    GenerationOptions {
+        temperature: Some(0.2),
+        num_predict: Some(32),
+        think: Some(false),
+        ..Default::default()
+    }
+}
+
+/// Reduces a model reply to a single clean line, or `None` if nothing usable is left.
+pub fn clean_title(reply: &str) -> Option<String> {
+    let line = reply.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())?;
+    let line = line.strip_prefix("Title:").unwrap_or(line);
+    let title = line
+        .trim_matches(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '*' | '#' | '`'))
+        .trim_end_matches(['.', '!', ':', ';', ','])
+        .trim();
+
+    if title.is_empty() {
+        return None;
+    }
+    if title.chars().count() <= MAX_TITLE_CHARS {
+        return Some(title.to_string());
+    }
+    let truncated: String = title.chars().take(MAX_TITLE_CHARS).collect();
+    Some(format!("{}…", truncated.trim_end()))
+}
+
+fn excerpt(text: &str) -> String {
+    text.trim().chars().take(MAX_EXCERPT_CHARS).collect()
+}
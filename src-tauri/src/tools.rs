@@ -0,0 +1,233 @@
+use crate::ollama::{ToolDefinition, ToolFunction};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+type ToolHandler = Box<dyn Fn(&Value) -> Result<String, String> + Send + Sync>;
+
+struct RegisteredTool {
+    definition: ToolDefinition,
+    handler: ToolHandler,
+}
+
+/// Rust functions the model is allowed to call, keyed by tool name.
+pub struct ToolRegistry {
+    tools: HashMap<String, RegisteredTool>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self {
+            tools: HashMap::new(),
+        }
+    }
+
+    /// Registry with the tools that ship with the app.
+    pub fn with_builtin_tools() -> Self {
+        let mut registry = Self::new();
+
+        registry.register(
+            "get_current_time",
+            "Get the current local date and time, including the timezone offset.",
+            json!({
+                "type": "object",
+                "properties": {},
+            }),
+            |_| Ok(chrono::Local::now().format("%Y-%m-%d %H:%M:%S %:z").to_string()),
+        );
+
+        registry.register(
+            "calculate",
+            "Evaluate an arithmetic expression using + - * / % ^ and parentheses.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "expression": {
+                        "type": "string",
+                        "description": "The expression to evaluate, e.g. (3 + 4) * 2.5",
+                    },
+                },
+                "required": ["expression"],
+            }),
+            |arguments| {
+                let expression = arguments
+                    .get("expression")
+                    .and_then(Value::as_str)
+                    .ok_or("Missing string argument 'expression'")?;
+                evaluate_expression(expression).map(|value| value.to_string())
+            },
+        );
+
+        registry
+    }
+
+    pub fn register<F>(&mut self, name: &str, description: &str, parameters: Value, handler: F)
+    where
+        F: Fn(&Value) -> Result<String, String> + Send + Sync + 'static,
+    {
+        let definition = ToolDefinition {
+            kind: "function".to_string(),
+            function: ToolFunction {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters,
+            },
+        };
+        self.tools.insert(name.to_string(), RegisteredTool {
+            definition,
+            handler: Box::new(handler),
+        });
+    }
+
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        let mut definitions: Vec<ToolDefinition> = self.tools.values()
+            .map(|tool| tool.definition.clone())
+            .collect();
+        definitions.sort_by(|a, b| a.function.name.cmp(&b.function.name));
+        definitions
+    }
+
+    /// Runs the named tool. Failures are returned as text so they can be fed
+    /// back to the model rather than aborting the conversation.
+    pub fn call(&self, name: &str, arguments: &Value) -> String {
+        match self.tools.get(name) {
+            Some(tool) => match (tool.handler)(arguments) {
+                Ok(output) => output,
+                Err(e) => format!("Error: {}", e),
+            },
+            None => format!("Error: unknown tool '{}'", name),
+        }
+    }
+}
+
+// The expression comes from the model, so bound both its size and how deep
+// the parser recurses into it
+const MAX_EXPRESSION_LEN: usize = 1000;
+const MAX_NESTING_DEPTH: usize = 64;
+
+/// Recursive-descent evaluator for the `calculate` tool.
+fn evaluate_expression(expression: &str) -> Result<f64, String> {
+    if expression.len() > MAX_EXPRESSION_LEN {
+        return Err(format!("Expression is longer than {} characters", MAX_EXPRESSION_LEN));
+    }
+    let tokens: Vec<char> = expression.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut position = 0;
+    let value = parse_sum(&tokens, &mut position, 0)?;
+    if position != tokens.len() {
+        return Err(format!("Unexpected '{}' in expression", tokens[position]));
+    }
+    if !value.is_finite() {
+        return Err("Result is not a finite number".to_string());
+    }
+    Ok(value)
+}
+
+// Depth for a nested parenthesis, unary sign or exponent
+fn nested(depth: usize) -> Result<usize, String> {
+    if depth >= MAX_NESTING_DEPTH {
+        return Err("Expression is nested too deeply".to_string());
+    }
+    Ok(depth + 1)
+}
+
+fn parse_sum(tokens: &[char], position: &mut usize, depth: usize) -> Result<f64, String> {
+    let mut value = parse_product(tokens, position, depth)?;
+    while let Some(&op) = tokens.get(*position) {
+        match op {
+            '+' => { *position += 1; value += parse_product(tokens, position, depth)?; }
+            '-' => { *position += 1; value -= parse_product(tokens, position, depth)?; }
+            _ => break,
+        }
+    }
+    Ok(value)
+}
+
+fn parse_product(tokens: &[char], position: &mut usize, depth: usize) -> Result<f64, String> {
+    let mut value = parse_unary(tokens, position, depth)?;
+    while let Some(&op) = tokens.get(*position) {
+        match op {
+            '*' => { *position += 1; value *= parse_unary(tokens, position, depth)?; }
+            '/' => { *position += 1; value /= parse_unary(tokens, position, depth)?; }
+            '%' => { *position += 1; value %= parse_unary(tokens, position, depth)?; }
+            _ => break,
+        }
+    }
+    Ok(value)
+}
+
+fn parse_unary(tokens: &[char], position: &mut usize, depth: usize) -> Result<f64, String> {
+    match tokens.get(*position) {
+        Some('-') => { *position += 1; Ok(-parse_unary(tokens, position, nested(depth)?)?) }
+        Some('+') => { *position += 1; parse_unary(tokens, position, nested(depth)?) }
+        _ => parse_power(tokens, position, depth),
+    }
+}
+
+// Binds tighter than a leading sign, so -2^2 == -(2^2)
+fn parse_power(tokens: &[char], position: &mut usize, depth: usize) -> Result<f64, String> {
+    let base = parse_atom(tokens, position, depth)?;
+    if tokens.get(*position) == Some(&'^') {
+        *position += 1;
+        // Right associative: 2^3^2 == 2^(3^2), and the exponent may be signed
+        let exponent = parse_unary(tokens, position, nested(depth)?)?;
+        return Ok(base.powf(exponent));
+    }
+    Ok(base)
+}
+
+fn parse_atom(tokens: &[char], position: &mut usize, depth: usize) -> Result<f64, String> {
+    match tokens.get(*position) {
+        Some('(') => {
+            *position += 1;
+            let value = parse_sum(tokens, position, nested(depth)?)?;
+            if tokens.get(*position) != Some(&')') {
+                return Err("Missing closing parenthesis".to_string());
+            }
+            *position += 1;
+            Ok(value)
+        }
+        Some(c) if c.is_ascii_digit() || *c == '.' => {
+            let start = *position;
+            while tokens.get(*position).is_some_and(|c| c.is_ascii_digit() || *c == '.') {
+                *position += 1;
+            }
+            let number: String = tokens[start..*position].iter().collect();
+            number.parse::<f64>().map_err(|_| format!("Invalid number '{}'", number))
+        }
+        Some(c) => Err(format!("Unexpected '{}' in expression", c)),
+        None => Err("Unexpected end of expression".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_precedence_and_unary_signs() {
+        assert_eq!(evaluate_expression("1 + 2 * 3"), Ok(7.0));
+        assert_eq!(evaluate_expression("(1 + 2) * 3"), Ok(9.0));
+        assert_eq!(evaluate_expression("2 ^ 3 ^ 2"), Ok(512.0));
+        assert_eq!(evaluate_expression("-2 ^ 2"), Ok(-4.0));
+        assert_eq!(evaluate_expression("2 ^ -1"), Ok(0.5));
+        assert_eq!(evaluate_expression("(-2) ^ 2"), Ok(4.0));
+        assert_eq!(evaluate_expression("--3 - +1"), Ok(2.0));
+        assert_eq!(evaluate_expression("10 % 4 / 2"), Ok(1.0));
+    }
+
+    #[test]
+    fn rejects_deep_nesting() {
+        let parens = format!("{}1{}", "(".repeat(MAX_NESTING_DEPTH), ")".repeat(MAX_NESTING_DEPTH));
+        assert_eq!(evaluate_expression(&parens), Ok(1.0));
+
+        let too_deep = format!("{}1{}", "(".repeat(MAX_NESTING_DEPTH + 1), ")".repeat(MAX_NESTING_DEPTH + 1));
+        assert_eq!(evaluate_expression(&too_deep), Err("Expression is nested too deeply".to_string()));
+        assert_eq!(evaluate_expression(&format!("{}1", "-".repeat(100))), Err("Expression is nested too deeply".to_string()));
+        assert_eq!(evaluate_expression(&format!("{}2", "2^".repeat(100))), Err("Expression is nested too deeply".to_string()));
+    }
+
+    #[test]
+    fn rejects_long_expressions() {
+        let long = "(".repeat(50_000);
+        assert!(evaluate_expression(&long).unwrap_err().contains("longer than"));
+    }
+}
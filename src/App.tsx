@@ -1,9 +1,11 @@
 import React, { useState, useEffect } from 'react';
-import { invoke } from '@tauri-apps/api/core';
+import { invoke, Channel } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import ChatWindow from './components/ChatWindow';
 import HistorySidebar from './components/HistorySidebar';
 import StatusBar from './components/StatusBar';
-import { Conversation, Message, OllamaModel } from './types';
+import UnlockScreen from './components/UnlockScreen';
+import { Conversation, EncryptionStatus, Message, OllamaModel, PullProgress, TitleUpdated } from './types';
 
 const App: React.FC = () => {
   const [conversations, setConversations] = useState<Conversation[]>([]);
@@ -11,6 +13,11 @@ const App: React.FC = () => {
   const [messages, setMessages] = useState<Message[]>([]);
   const [availableModels, setAvailableModels] = useState<OllamaModel[]>([]);
   const [selectedModel, setSelectedModel] = useState<string>('gemma3:4b');
+  const [pullName, setPullName] = useState('');
+  const [pullingModel, setPullingModel] = useState<string | null>(null);
+  const [pullProgress, setPullProgress] = useState<PullProgress | null>(null);
+  // null until the backend has said whether the database needs a passphrase
+  const [locked, setLocked] = useState<boolean | null>(null);
 
   const loadConversations = async () => {
     try {
@@ -47,6 +54,49 @@ const App: React.FC = () => {
     }
   };
 
+  const pullModel = async () => {
+    const model = pullName.trim();
+    if (!model || pullingModel) return;
+
+    setPullingModel(model);
+    setPullProgress(null);
+    try {
+      const onProgress = new Channel<PullProgress>();
+      onProgress.onmessage = (progress) => setPullProgress(progress);
+
+      const models = await invoke<OllamaModel[]>('pull_model', { model, onProgress });
+      setAvailableModels(models);
+      setSelectedModel(model);
+      setPullName('');
+    } catch (error) {
+      if (error !== 'PULL_CANCELLED') {
+        console.error('Error pulling model:', error);
+        alert(`Error pulling model: ${error}`);
+      }
+    } finally {
+      setPullingModel(null);
+      setPullProgress(null);
+    }
+  };
+
+  const cancelPull = async () => {
+    if (!pullingModel) return;
+    try {
+      await invoke<boolean>('cancel_pull', { model: pullingModel });
+    } catch (error) {
+      console.error('Error cancelling pull:', error);
+    }
+  };
+
+  const formatPullProgress = (progress: PullProgress | null) => {
+    if (!progress) return 'Starting...';
+    if (progress.total && progress.completed !== undefined) {
+      const percent = Math.floor((progress.completed / progress.total) * 100);
+      return `${progress.status} (${percent}%)`;
+    }
+    return progress.status;
+  };
+
   const loadMessages = async (conversationId: number) => {
     try {
       const msgs = await invoke<Message[]>('get_messages', { conversationId });
@@ -66,17 +116,62 @@ const App: React.FC = () => {
     if (selectedConversationId) {
       loadMessages(selectedConversationId);
     }
+    // New messages move the conversation to the top of the sidebar
+    loadConversations();
   };
 
   const handleConversationsUpdate = () => {
     loadConversations();
   };
 
-  useEffect(() => {
+  const handleConversationDeleted = (id: number) => {
+    if (selectedConversationId === id) {
+      setSelectedConversationId(null);
+      setMessages([]);
+    }
+    loadConversations();
+  };
+
+  const handleUnlocked = () => {
+    setLocked(false);
     loadConversations();
     loadModels();
+  };
+
+  useEffect(() => {
+    invoke<EncryptionStatus>('get_encryption_status')
+      .then((status) => {
+        if (status.locked) {
+          setLocked(true);
+        } else {
+          handleUnlocked();
+        }
+      })
+      .catch((error) => console.error('Error checking encryption status:', error));
   }, []);
 
+  // Titles are generated in the background after the first exchange
+  useEffect(() => {
+    const unlisten = listen<TitleUpdated>('conversation-title-updated', (event) => {
+      const { conversation_id, title } = event.payload;
+      setConversations((current) =>
+        current.map((conversation) =>
+          conversation.id === conversation_id ? { ...conversation, title } : conversation
+        )
+      );
+    });
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, []);
+
+  if (locked === null) {
+    return <div className="h-screen bg-gray-50" />;
+  }
+  if (locked) {
+    return <UnlockScreen onUnlocked={handleUnlocked} />;
+  }
+
   return (
     <div className="h-screen flex flex-col bg-gray-50">
       {/* Header */}
@@ -112,6 +207,36 @@ const App: React.FC = () => {
                   </svg>
                   Refresh
                 </button>
+                {pullingModel ? (
+                  <div className="flex items-center space-x-2">
+                    <span className="text-sm text-gray-600">
+                      Pulling {pullingModel}: {formatPullProgress(pullProgress)}
+                    </span>
+                    <button
+                      onClick={cancelPull}
+                      className="px-3 py-2 text-sm bg-gray-100 hover:bg-gray-200 rounded-lg transition-colors border border-gray-300"
+                    >
+                      Cancel
+                    </button>
+                  </div>
+                ) : (
+                  <div className="flex items-center space-x-2">
+                    <input
+                      value={pullName}
+                      onChange={(e) => setPullName(e.target.value)}
+                      onKeyDown={(e) => e.key === 'Enter' && pullModel()}
+                      placeholder="e.g. llama3.2:3b"
+                      className="px-3 py-2 border border-gray-300 rounded-lg text-sm focus:outline-none focus:ring-2 focus:ring-blue-500 focus:border-transparent bg-white"
+                    />
+                    <button
+                      onClick={pullModel}
+                      disabled={!pullName.trim()}
+                      className="px-3 py-2 text-sm bg-gray-100 hover:bg-gray-200 disabled:opacity-50 rounded-lg transition-colors border border-gray-300"
+                    >
+                      Pull
+                    </button>
+                  </div>
+                )}
               </div>
             </div>
           </div>
@@ -125,6 +250,7 @@ const App: React.FC = () => {
           selectedConversationId={selectedConversationId}
           onConversationSelect={handleConversationSelect}
           onConversationsUpdate={handleConversationsUpdate}
+          onConversationDeleted={handleConversationDeleted}
         />
         <div className="flex-1 flex flex-col bg-white">
           <ChatWindow
@@ -1,7 +1,8 @@
 import React, { useState, useEffect, useRef } from 'react';
-import { invoke } from '@tauri-apps/api/core';
-import { Message, ChatMessage, ImageFile } from '../types';
+import { invoke, Channel } from '@tauri-apps/api/core';
+import { Attachment, Message, ChatStreamEvent, ContextWindow, ImageFile } from '../types';
 import ImageUpload from './ImageUpload';
+import StoredImage from './StoredImage';
 
 interface ChatWindowProps {
   conversationId: number | null;
@@ -13,8 +14,13 @@ interface ChatWindowProps {
 const ChatWindow: React.FC<ChatWindowProps> = ({ conversationId, messages, selectedModel, onMessagesUpdate }) => {
   const [input, setInput] = useState('');
   const [isLoading, setIsLoading] = useState(false);
-  const [selectedImage, setSelectedImage] = useState<ImageFile | null>(null);
+  const [streamingText, setStreamingText] = useState('');
+  const [streamingThinking, setStreamingThinking] = useState('');
+  const [selectedImages, setSelectedImages] = useState<ImageFile[]>([]);
   const [showVisionError, setShowVisionError] = useState(false);
+  const [editingId, setEditingId] = useState<number | null>(null);
+  const [editText, setEditText] = useState('');
+  const [contextWindow, setContextWindow] = useState<ContextWindow | null>(null);
   const messagesEndRef = useRef<HTMLDivElement>(null);
   const textareaRef = useRef<HTMLTextAreaElement>(null);
 
@@ -24,101 +30,168 @@ const ChatWindow: React.FC<ChatWindowProps> = ({ conversationId, messages, selec
 
   useEffect(() => {
     scrollToBottom();
-  }, [messages]);
+  }, [messages, streamingText, streamingThinking]);
+
+  // Find out which messages the next reply will no longer see
+  useEffect(() => {
+    if (!conversationId || !selectedModel || messages.length === 0) {
+      setContextWindow(null);
+      return;
+    }
+    invoke<ContextWindow>('get_context_window', { conversationId, model: selectedModel })
+      .then(setContextWindow)
+      .catch(error => console.error('Error loading context window:', error));
+  }, [conversationId, messages, selectedModel]);
+
+  const droppedIds = new Set(contextWindow?.dropped_message_ids ?? []);
 
   const handleSubmit = async (e: React.FormEvent) => {
     e.preventDefault();
-    if ((!input.trim() && !selectedImage) || !conversationId || isLoading) return;
+    if ((!input.trim() && selectedImages.length === 0) || !conversationId || isLoading) return;
 
     const userMessage = input.trim();
-    const hasImage = selectedImage !== null;
+    const images = selectedImages;
+    const hasImage = images.length > 0;
     
     setInput('');
     setIsLoading(true);
     setShowVisionError(false);
 
     try {
-      let imagePath: string | null = null;
-      
-      // Save image if present
-      if (selectedImage) {
-        imagePath = await invoke<string>('save_image_file', {
+      // Save images if present
+      const attachments: Attachment[] = [];
+      for (const image of images) {
+        const path = await invoke<string>('save_image_file', {
           conversationId,
-          imageData: selectedImage.base64,
-          filename: selectedImage.file.name,
+          imageData: image.base64,
+          filename: image.file.name,
         });
+        attachments.push({ path, filename: image.file.name, size: image.file.size });
       }
 
       // Save user message
-      await invoke('save_message_with_image', {
+      await invoke('save_message_with_attachments', {
         conversationId,
         role: 'user',
-        content: userMessage || (hasImage ? 'Uploaded an image' : ''),
+        content: userMessage || (hasImage ? 'What do you see in this image?' : ''),
         inputType: hasImage ? (userMessage ? 'mixed' : 'image') : 'text',
-        imagePath,
-        imageFilename: selectedImage?.file.name || null,
-        imageSize: selectedImage?.file.size || null,
+        attachments,
       });
 
-      // Prepare chat history
-      const chatHistory: ChatMessage[] = messages.map(msg => ({
-        role: msg.role,
-        content: msg.content,
-        images: msg.image_path ? [msg.image_path] : undefined,
-      }));
-      
-      // Add current message to history
-      const currentMessage: ChatMessage = {
-        role: 'user',
-        content: userMessage || (hasImage ? 'What do you see in this image?' : ''),
-      };
-      
-      if (hasImage && imagePath) {
-        currentMessage.images = [imagePath];
-      }
-      
-      chatHistory.push(currentMessage);
+      // Clear image selection
+      images.forEach(image => URL.revokeObjectURL(image.preview));
+      setSelectedImages([]);
+      onMessagesUpdate();
 
-      let response: string;
-      
-      // Send to Ollama
-      if (hasImage && imagePath) {
-        response = await invoke<string>('send_prompt_with_image', {
-          prompt: userMessage || 'What do you see in this image?',
-          imagePath,
-          model: selectedModel,
-        });
-      } else {
-        response = await invoke<string>('send_prompt_with_history', {
-          messages: chatHistory,
-          model: selectedModel,
-        });
-      }
+      // The backend rebuilds the history, earlier images included, and saves the reply
+      const onEvent = new Channel<ChatStreamEvent>();
+      onEvent.onmessage = (message) => {
+        if (message.event === 'context') {
+          setContextWindow(message.data.window);
+        } else if (message.event === 'thinking') {
+          setStreamingThinking(prev => prev + message.data.content);
+        } else if (message.event === 'delta') {
+          setStreamingText(prev => prev + message.data.content);
+        }
+      };
 
-      // Save assistant response
-      await invoke('save_message', {
+      await invoke<string>('stream_conversation_reply', {
         conversationId,
-        role: 'assistant',
-        content: response,
+        model: selectedModel,
+        onEvent,
       });
 
-      // Clear image selection
-      if (selectedImage) {
-        URL.revokeObjectURL(selectedImage.preview);
-        setSelectedImage(null);
-      }
-
       onMessagesUpdate();
     } catch (error) {
       console.error('Error sending message:', error);
       
       if (error === 'VISION_NOT_SUPPORTED') {
         setShowVisionError(true);
+      } else if (error === 'GENERATION_CANCELLED') {
+        // The partial reply was saved by the backend
+        onMessagesUpdate();
       } else {
         alert(`Error: ${error}`);
       }
     } finally {
       setIsLoading(false);
+      setStreamingText('');
+      setStreamingThinking('');
+    }
+  };
+
+  // Runs a command that streams a reply and saves it on the backend
+  const streamBranchReply = async (command: string, args: Record<string, unknown>) => {
+    setIsLoading(true);
+    try {
+      const onEvent = new Channel<ChatStreamEvent>();
+      onEvent.onmessage = (event) => {
+        if (event.event === 'context') {
+          setContextWindow(event.data.window);
+        } else if (event.event === 'thinking') {
+          setStreamingThinking(prev => prev + event.data.content);
+        } else if (event.event === 'delta') {
+          setStreamingText(prev => prev + event.data.content);
+        }
+      };
+
+      await invoke<string>(command, { ...args, onEvent });
+      onMessagesUpdate();
+    } catch (error) {
+      if (error === 'GENERATION_CANCELLED') {
+        onMessagesUpdate();
+      } else {
+        console.error(`Error running ${command}:`, error);
+        alert(`Error: ${error}`);
+        onMessagesUpdate();
+      }
+    } finally {
+      setIsLoading(false);
+      setStreamingText('');
+      setStreamingThinking('');
+    }
+  };
+
+  const handleEditSubmit = async (message: Message) => {
+    const content = editText.trim();
+    if (!content || isLoading) return;
+
+    setEditingId(null);
+    // Saves the edit as a new branch and the reply to it
+    await streamBranchReply('edit_and_resend', {
+      messageId: message.id,
+      content,
+      model: selectedModel,
+    });
+  };
+
+  const handleRegenerate = async (message: Message) => {
+    if (isLoading) return;
+    // The new reply is kept as another version alongside this one
+    await streamBranchReply('regenerate_message', {
+      messageId: message.id,
+      model: selectedModel,
+    });
+  };
+
+  const switchSibling = async (message: Message, step: number) => {
+    try {
+      const siblings = await invoke<Message[]>('list_message_siblings', { messageId: message.id });
+      const target = siblings[message.sibling_index - 1 + step];
+      if (!target) return;
+      await invoke('switch_branch', { messageId: target.id });
+      onMessagesUpdate();
+    } catch (error) {
+      console.error('Error switching branch:', error);
+    }
+  };
+
+  const handleCancel = async () => {
+    if (!conversationId) return;
+    try {
+      await invoke<boolean>('cancel_generation', { conversationId });
+    } catch (error) {
+      console.error('Error cancelling generation:', error);
     }
   };
 
@@ -130,14 +203,12 @@ const ChatWindow: React.FC<ChatWindowProps> = ({ conversationId, messages, selec
   };
 
   const handleImageSelect = (imageFile: ImageFile) => {
-    setSelectedImage(imageFile);
+    setSelectedImages(prev => [...prev, imageFile]);
   };
 
-  const handleImageRemove = () => {
-    if (selectedImage) {
-      URL.revokeObjectURL(selectedImage.preview);
-      setSelectedImage(null);
-    }
+  const handleImageRemove = (imageFile: ImageFile) => {
+    URL.revokeObjectURL(imageFile.preview);
+    setSelectedImages(prev => prev.filter(image => image !== imageFile));
   };
 
   const adjustTextareaHeight = () => {
@@ -154,10 +225,8 @@ const ChatWindow: React.FC<ChatWindowProps> = ({ conversationId, messages, selec
 
   useEffect(() => {
     return () => {
-      // Cleanup image preview URL on unmount
-      if (selectedImage) {
-        URL.revokeObjectURL(selectedImage.preview);
-      }
+      // Cleanup image preview URLs on unmount
+      selectedImages.forEach(image => URL.revokeObjectURL(image.preview));
     };
   }, []);
 
@@ -182,7 +251,9 @@ const ChatWindow: React.FC<ChatWindowProps> = ({ conversationId, messages, selec
             key={message.id}
             className={`flex ${message.role === 'user' ? 'justify-end' : 'justify-start'}`}
           >
-            <div className={`max-w-2xl ${message.role === 'user' ? 'ml-12' : 'mr-12'}`}>
+            <div className={`max-w-2xl ${message.role === 'user' ? 'ml-12' : 'mr-12'} ${
+              droppedIds.has(message.id) ? 'opacity-50' : ''
+            }`}>
               <div
                 className={`px-4 py-3 rounded-2xl shadow-sm ${
                   message.role === 'user'
@@ -191,51 +262,163 @@ const ChatWindow: React.FC<ChatWindowProps> = ({ conversationId, messages, selec
                 }`}
               >
                 {/* Image Display */}
-                {message.image_path && (
-                  <div className="mb-3">
-                    <img
-                      src={`file://${message.image_path}`}
-                      alt={message.image_filename || 'Uploaded image'}
+                {message.attachments.map((attachment) => (
+                  <div key={attachment.path} className="mb-3">
+                    <StoredImage
+                      path={attachment.path}
+                      alt={attachment.filename || 'Uploaded image'}
                       className="max-w-full h-auto rounded-lg cursor-pointer hover:opacity-90 transition-opacity"
                       style={{ maxHeight: '300px' }}
-                      onClick={() => {
-                        // TODO: Open image in modal
-                        window.open(`file://${message.image_path}`, '_blank');
-                      }}
                     />
-                    {message.image_filename && (
+                    {attachment.filename && (
                       <p className={`text-xs mt-1 ${
                         message.role === 'user' ? 'text-blue-100' : 'text-gray-500'
                       }`}>
-                        {message.image_filename}
+                        {attachment.filename}
                       </p>
                     )}
                   </div>
-                )}
+                ))}
                 
+                {/* Reasoning, collapsed by default */}
+                {message.reasoning && (
+                  <details className="mb-2 text-sm text-gray-500">
+                    <summary className="cursor-pointer select-none">Thinking</summary>
+                    <div className="mt-1 pl-3 border-l-2 border-gray-300 whitespace-pre-wrap leading-relaxed">
+                      {message.reasoning}
+                    </div>
+                  </details>
+                )}
+
                 {/* Message Content */}
-                {message.content && (
+                {editingId === message.id ? (
+                  <div className="space-y-2">
+                    <textarea
+                      autoFocus
+                      value={editText}
+                      onChange={(e) => setEditText(e.target.value)}
+                      rows={3}
+                      className="w-full min-w-[16rem] px-3 py-2 rounded-lg text-gray-900 focus:outline-none focus:ring-2 focus:ring-blue-200"
+                    />
+                    <div className="flex justify-end space-x-2 text-sm">
+                      <button
+                        onClick={() => setEditingId(null)}
+                        className="px-3 py-1 rounded-md text-blue-100 hover:text-white"
+                      >
+                        Cancel
+                      </button>
+                      <button
+                        onClick={() => handleEditSubmit(message)}
+                        disabled={!editText.trim() || isLoading}
+                        className="px-3 py-1 rounded-md bg-white text-blue-600 font-medium hover:bg-blue-50 disabled:opacity-50"
+                      >
+                        Save & resend
+                      </button>
+                    </div>
+                  </div>
+                ) : message.content && (
                   <div className="whitespace-pre-wrap leading-relaxed">
                     {message.content}
                   </div>
                 )}
+                {message.status === 'cancelled' && (
+                  <p className="text-xs mt-2 italic text-gray-500">Generation stopped</p>
+                )}
+                {droppedIds.has(message.id) && (
+                  <p
+                    className={`text-xs mt-2 italic ${message.role === 'user' ? 'text-blue-100' : 'text-gray-500'}`}
+                    title={contextWindow?.summary_error ?? undefined}
+                  >
+                    {contextWindow?.summarized
+                      ? 'Summarized for the model'
+                      : contextWindow?.summary_error
+                        ? "Outside the model's context (summarizing failed)"
+                        : "Outside the model's context"}
+                  </p>
+                )}
               </div>
               
-              {/* Timestamp */}
-              <div className={`text-xs mt-2 ${
-                message.role === 'user' ? 'text-right text-gray-400' : 'text-left text-gray-400'
+              {/* Timestamp, branch navigation and edit */}
+              <div className={`flex items-center space-x-2 text-xs mt-2 text-gray-400 ${
+                message.role === 'user' ? 'justify-end' : 'justify-start'
               }`}>
-                {new Date(message.timestamp).toLocaleTimeString([], { 
-                  hour: '2-digit', 
-                  minute: '2-digit' 
-                })}
+                {message.sibling_count > 1 && (
+                  <div className="flex items-center space-x-1">
+                    <button
+                      onClick={() => switchSibling(message, -1)}
+                      disabled={isLoading || message.sibling_index <= 1}
+                      className="px-1 hover:text-gray-600 disabled:opacity-40"
+                    >
+                      ‹
+                    </button>
+                    <span>{message.sibling_index}/{message.sibling_count}</span>
+                    <button
+                      onClick={() => switchSibling(message, 1)}
+                      disabled={isLoading || message.sibling_index >= message.sibling_count}
+                      className="px-1 hover:text-gray-600 disabled:opacity-40"
+                    >
+                      ›
+                    </button>
+                  </div>
+                )}
+                {message.role === 'assistant' && (
+                  <button
+                    onClick={() => handleRegenerate(message)}
+                    disabled={isLoading}
+                    title={`Regenerate with ${selectedModel}`}
+                    className="hover:text-gray-600 disabled:opacity-40"
+                  >
+                    Regenerate
+                  </button>
+                )}
+                {message.role === 'user' && editingId !== message.id && (
+                  <button
+                    onClick={() => {
+                      setEditingId(message.id);
+                      setEditText(message.content);
+                    }}
+                    disabled={isLoading}
+                    className="hover:text-gray-600 disabled:opacity-40"
+                  >
+                    Edit
+                  </button>
+                )}
+                <span>
+                  {new Date(message.timestamp).toLocaleTimeString([], { 
+                    hour: '2-digit', 
+                    minute: '2-digit' 
+                  })}
+                </span>
               </div>
             </div>
           </div>
         ))}
         
+        {/* Streaming Reply */}
+        {isLoading && (streamingText || streamingThinking) && (
+          <div className="flex justify-start">
+            <div className="max-w-2xl mr-12">
+              <div className="px-4 py-3 rounded-2xl shadow-sm bg-gray-100 text-gray-900 border border-gray-200">
+                {streamingThinking && (
+                  <details open={!streamingText} className="mb-2 text-sm text-gray-500">
+                    <summary className="cursor-pointer select-none">
+                      {streamingText ? 'Thinking' : 'Thinking...'}
+                    </summary>
+                    <div className="mt-1 pl-3 border-l-2 border-gray-300 whitespace-pre-wrap leading-relaxed">
+                      {streamingThinking}
+                    </div>
+                  </details>
+                )}
+                <div className="whitespace-pre-wrap leading-relaxed">
+                  {streamingText}
+                </div>
+              </div>
+            </div>
+          </div>
+        )}
+
         {/* Loading Indicator */}
-        {isLoading && (
+        {isLoading && !streamingText && !streamingThinking && (
           <div className="flex justify-start">
             <div className="mr-12">
               <div className="bg-gray-100 text-gray-900 px-4 py-3 rounded-2xl shadow-sm border border-gray-200">
@@ -285,31 +468,32 @@ const ChatWindow: React.FC<ChatWindowProps> = ({ conversationId, messages, selec
       {/* Input Area */}
       <div className="border-t border-gray-200 bg-white">
         <div className="p-4">
-          {/* Image Preview */}
-          {selectedImage && (
-            <div className="mb-3">
-              <ImageUpload
-                onImageSelect={handleImageSelect}
-                onImageRemove={handleImageRemove}
-                selectedImage={selectedImage}
-                disabled={!conversationId || isLoading}
-              />
+          {/* Image Previews */}
+          {selectedImages.length > 0 && (
+            <div className="mb-3 flex flex-wrap gap-3">
+              {selectedImages.map((image) => (
+                <ImageUpload
+                  key={image.preview}
+                  onImageSelect={handleImageSelect}
+                  onImageRemove={() => handleImageRemove(image)}
+                  selectedImage={image}
+                  disabled={!conversationId || isLoading}
+                />
+              ))}
             </div>
           )}
           
           {/* Input Form */}
           <form onSubmit={handleSubmit} className="flex items-end space-x-3">
             {/* Image Upload Button */}
-            {!selectedImage && (
-              <div className="flex-shrink-0">
-                <ImageUpload
-                  onImageSelect={handleImageSelect}
-                  onImageRemove={handleImageRemove}
-                  selectedImage={selectedImage}
-                  disabled={!conversationId || isLoading}
-                />
-              </div>
-            )}
+            <div className="flex-shrink-0">
+              <ImageUpload
+                onImageSelect={handleImageSelect}
+                onImageRemove={() => {}}
+                selectedImage={null}
+                disabled={!conversationId || isLoading}
+              />
+            </div>
             
             {/* Text Input */}
             <div className="flex-1 relative">
@@ -329,11 +513,28 @@ const ChatWindow: React.FC<ChatWindowProps> = ({ conversationId, messages, selec
               />
             </div>
             
+            {/* Stop Button */}
+            {/* Also shown before the first token, while the history may be summarized */}
+            {isLoading && (
+              <div className="flex-shrink-0">
+                <button
+                  type="button"
+                  onClick={handleCancel}
+                  className="w-12 h-12 bg-gray-200 text-gray-700 rounded-full hover:bg-gray-300 transition-colors flex items-center justify-center"
+                  title="Stop generating"
+                >
+                  <svg width="16" height="16" viewBox="0 0 24 24" fill="currentColor">
+                    <rect x="5" y="5" width="14" height="14" rx="2"></rect>
+                  </svg>
+                </button>
+              </div>
+            )}
+
             {/* Send Button */}
             <div className="flex-shrink-0">
               <button
                 type="submit"
-                disabled={(!input.trim() && !selectedImage) || !conversationId || isLoading}
+                disabled={(!input.trim() && selectedImages.length === 0) || !conversationId || isLoading}
                 className="w-12 h-12 bg-blue-500 text-white rounded-full hover:bg-blue-600 disabled:bg-gray-300 disabled:cursor-not-allowed transition-colors flex items-center justify-center"
               >
                 {isLoading ? (
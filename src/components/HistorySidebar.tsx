@@ -1,12 +1,13 @@
-import React from 'react';
+import React, { useEffect, useRef, useState } from 'react';
 import { invoke } from '@tauri-apps/api/core';
-import { Conversation } from '../types';
+import { Conversation, EncryptionReport, EncryptionStatus, ExportFormat, ImportReport, RemovalFailure, RemovalReport, RestoreReport } from '../types';
 
 interface HistorySidebarProps {
   conversations: Conversation[];
   selectedConversationId: number | null;
   onConversationSelect: (id: number) => void;
   onConversationsUpdate: () => void;
+  onConversationDeleted: (id: number) => void;
 }
 
 const HistorySidebar: React.FC<HistorySidebarProps> = ({
@@ -14,6 +15,7 @@ const HistorySidebar: React.FC<HistorySidebarProps> = ({
   selectedConversationId,
   onConversationSelect,
   onConversationsUpdate,
+  onConversationDeleted,
 }) => {
   const handleNewConversation = async () => {
     try {
@@ -26,6 +28,161 @@ const HistorySidebar: React.FC<HistorySidebarProps> = ({
     }
   };
 
+  const [editingId, setEditingId] = useState<number | null>(null);
+  const [draftTitle, setDraftTitle] = useState('');
+  const [exportFormat, setExportFormat] = useState<ExportFormat>('markdown');
+  const [includeReasoning, setIncludeReasoning] = useState(true);
+  const [encrypted, setEncrypted] = useState(false);
+
+  useEffect(() => {
+    invoke<EncryptionStatus>('get_encryption_status')
+      .then((status) => setEncrypted(status.encrypted))
+      .catch((error) => console.error('Error checking encryption status:', error));
+  }, []);
+
+  const importInputRef = useRef<HTMLInputElement>(null);
+
+  const handleImport = async (e: React.ChangeEvent<HTMLInputElement>) => {
+    const file = e.target.files?.[0];
+    e.target.value = '';
+    if (!file) return;
+    try {
+      const report = await invoke<ImportReport>('import_conversations', { contents: await file.text() });
+      const lines = [
+        `Imported ${report.conversations_imported} conversation(s) and ${report.messages_imported} message(s) from ${report.source}.`,
+        `Skipped ${report.duplicates_skipped} duplicate(s) and ${report.empty_skipped} empty conversation(s).`,
+        ...report.failures,
+      ];
+      alert(lines.join('\n'));
+      onConversationsUpdate();
+    } catch (error) {
+      console.error('Error importing:', error);
+      alert(`Error importing: ${error}`);
+    }
+  };
+
+  const handleExport = async (conversationId: number | null) => {
+    try {
+      // Without a destination the backend writes into the app's exports folder
+      const path = conversationId === null
+        ? await invoke<string>('export_all', { format: exportFormat, includeReasoning })
+        : await invoke<string>('export_conversation', { conversationId, format: exportFormat, includeReasoning });
+      alert(`Exported to ${path}`);
+    } catch (error) {
+      console.error('Error exporting:', error);
+      alert(`Error exporting: ${error}`);
+    }
+  };
+
+  const handleBackup = async () => {
+    try {
+      const path = await invoke<string>('create_backup');
+      alert(`Backup saved to ${path}`);
+    } catch (error) {
+      console.error('Error creating backup:', error);
+      alert(`Error creating backup: ${error}`);
+    }
+  };
+
+  const handleRestore = async () => {
+    const path = prompt('Path of the backup (.zip) to restore:');
+    if (!path?.trim()) return;
+    const passphrase = prompt('Passphrase of the backup (leave empty if it is not encrypted):');
+    if (passphrase === null) return;
+    if (!confirm('Restoring replaces all conversations and images. The current data will be kept next to the restored copy. Continue?')) return;
+    try {
+      const report = await invoke<RestoreReport>('restore_backup', { path: path.trim(), passphrase: passphrase || null });
+      setEncrypted(report.encrypted);
+      alert(
+        `Restored ${report.conversations} conversation(s) and ${report.images} image(s) from the backup of ${report.backup_created_at}.\n` +
+        `Previous database kept at ${report.previous_database}`
+      );
+      if (selectedConversationId !== null) {
+        onConversationDeleted(selectedConversationId);
+      } else {
+        onConversationsUpdate();
+      }
+    } catch (error) {
+      console.error('Error restoring backup:', error);
+      alert(`Error restoring backup: ${error}`);
+    }
+  };
+
+  const handleEncryption = async () => {
+    try {
+      if (encrypted) {
+        const current = prompt('Current passphrase:');
+        if (!current) return;
+        const next = prompt('New passphrase:');
+        if (!next) return;
+        if (prompt('Repeat the new passphrase:') !== next) {
+          alert('Passphrases do not match.');
+          return;
+        }
+        await invoke('change_passphrase', { current, new: next });
+        alert('Passphrase changed.');
+        return;
+      }
+
+      const passphrase = prompt('Choose a passphrase. It will be asked for every time the app starts and cannot be recovered:');
+      if (!passphrase) return;
+      if (prompt('Repeat the passphrase:') !== passphrase) {
+        alert('Passphrases do not match.');
+        return;
+      }
+      const report = await invoke<EncryptionReport>('enable_encryption', { passphrase });
+      setEncrypted(true);
+      alert(`Database encrypted, along with ${report.images_encrypted} image(s).`);
+
+      // Older backups stay readable without the passphrase until removed
+      if (report.plain_copies.length > 0 && confirm(
+        `These unencrypted backup copies are still in the data folder:\n\n${report.plain_copies.join('\n')}\n\nDelete them?`
+      )) {
+        const removal = await invoke<RemovalReport>('remove_plain_copies', { paths: report.plain_copies });
+        alert(
+          `Removed ${removal.removed.length} unencrypted backup copies.` +
+          removal.failed.map((failure) => `\nCould not remove ${failure.path}: ${failure.error}`).join('')
+        );
+      }
+    } catch (error) {
+      console.error('Error updating encryption:', error);
+      alert(`Error updating encryption: ${error}`);
+    }
+  };
+
+  const startRename = (conversation: Conversation) => {
+    setEditingId(conversation.id);
+    setDraftTitle(conversation.title ?? '');
+  };
+
+  const commitRename = async () => {
+    if (editingId === null) return;
+    const id = editingId;
+    setEditingId(null);
+    try {
+      await invoke('rename_conversation', { conversationId: id, title: draftTitle });
+      onConversationsUpdate();
+    } catch (error) {
+      console.error('Error renaming conversation:', error);
+      alert(`Error renaming conversation: ${error}`);
+    }
+  };
+
+  const handleDelete = async (conversation: Conversation) => {
+    const name = conversation.title || `Conversation #${conversation.id}`;
+    if (!confirm(`Delete "${name}" and all of its messages and images?`)) return;
+    try {
+      const failed = await invoke<RemovalFailure[]>('delete_conversation', { conversationId: conversation.id });
+      onConversationDeleted(conversation.id);
+      if (failed.length > 0) {
+        alert(failed.map((failure) => `Could not remove ${failure.path}: ${failure.error}`).join('\n'));
+      }
+    } catch (error) {
+      console.error('Error deleting conversation:', error);
+      alert(`Error deleting conversation: ${error}`);
+    }
+  };
+
   const formatDate = (dateString: string) => {
     const date = new Date(dateString);
     const now = new Date();
@@ -87,22 +244,57 @@ const HistorySidebar: React.FC<HistorySidebarProps> = ({
               >
                 <div className="flex items-start justify-between">
                   <div className="flex-1 min-w-0">
-                    <div className={`font-medium text-sm truncate ${
-                      selectedConversationId === conversation.id
-                        ? 'text-blue-900'
-                        : 'text-gray-900 group-hover:text-gray-800'
-                    }`}>
-                      Conversation #{conversation.id}
-                    </div>
+                    {editingId === conversation.id ? (
+                      <input
+                        autoFocus
+                        value={draftTitle}
+                        onChange={(e) => setDraftTitle(e.target.value)}
+                        onClick={(e) => e.stopPropagation()}
+                        onBlur={commitRename}
+                        onKeyDown={(e) => {
+                          if (e.key === 'Enter') commitRename();
+                          if (e.key === 'Escape') setEditingId(null);
+                        }}
+                        placeholder={`Conversation #${conversation.id}`}
+                        className="w-full text-sm font-medium px-2 py-1 border border-blue-300 rounded-md focus:outline-none focus:ring-2 focus:ring-blue-200"
+                      />
+                    ) : (
+                      <div
+                        onDoubleClick={() => startRename(conversation)}
+                        title="Double-click to rename"
+                        className={`font-medium text-sm truncate ${
+                          selectedConversationId === conversation.id
+                            ? 'text-blue-900'
+                            : 'text-gray-900 group-hover:text-gray-800'
+                        }`}
+                      >
+                        {conversation.title || `Conversation #${conversation.id}`}
+                      </div>
+                    )}
                     <div className={`text-xs mt-1 ${
                       selectedConversationId === conversation.id
                         ? 'text-blue-600'
                         : 'text-gray-500'
                     }`}>
-                      {formatDate(conversation.created_at)}
+                      {formatDate(conversation.updated_at)}
                     </div>
                   </div>
                   
+                  <button
+                    onClick={(e) => {
+                      e.stopPropagation();
+                      handleDelete(conversation);
+                    }}
+                    title="Delete conversation"
+                    className="flex-shrink-0 ml-2 p-1 rounded-md text-gray-400 opacity-0 group-hover:opacity-100 hover:text-red-500 hover:bg-red-50 transition-all duration-200"
+                  >
+                    <svg width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" strokeWidth="2">
+                      <polyline points="3 6 5 6 21 6"></polyline>
+                      <path d="M19 6l-1 14a2 2 0 0 1-2 2H8a2 2 0 0 1-2-2L5 6"></path>
+                      <path d="M10 11v6M14 11v6M9 6V4a1 1 0 0 1 1-1h4a1 1 0 0 1 1 1v2"></path>
+                    </svg>
+                  </button>
+
                   {/* Active indicator */}
                   {selectedConversationId === conversation.id && (
                     <div className="flex-shrink-0 ml-2">
@@ -124,7 +316,73 @@ const HistorySidebar: React.FC<HistorySidebarProps> = ({
       </div>
       
       {/* Footer */}
-      <div className="p-4 border-t border-gray-200 bg-gray-50">
+      <div className="p-4 border-t border-gray-200 bg-gray-50 space-y-3">
+        <div className="flex items-center space-x-2 text-xs">
+          <select
+            value={exportFormat}
+            onChange={(e) => setExportFormat(e.target.value as ExportFormat)}
+            className="px-2 py-1 border border-gray-200 rounded-md bg-white text-gray-700"
+          >
+            <option value="markdown">Markdown</option>
+            <option value="json">JSON</option>
+            <option value="html">HTML</option>
+          </select>
+          <button
+            onClick={() => handleExport(selectedConversationId)}
+            disabled={selectedConversationId === null}
+            className="flex-1 px-2 py-1 rounded-md bg-white border border-gray-200 text-gray-700 hover:bg-gray-100 disabled:opacity-50"
+          >
+            Export current
+          </button>
+          <button
+            onClick={() => handleExport(null)}
+            disabled={conversations.length === 0}
+            className="flex-1 px-2 py-1 rounded-md bg-white border border-gray-200 text-gray-700 hover:bg-gray-100 disabled:opacity-50"
+          >
+            Export all
+          </button>
+        </div>
+        <label className="flex items-center space-x-2 text-xs text-gray-600">
+          <input
+            type="checkbox"
+            checked={includeReasoning}
+            onChange={(e) => setIncludeReasoning(e.target.checked)}
+          />
+          <span>Include model reasoning in exports</span>
+        </label>
+        <button
+          onClick={() => importInputRef.current?.click()}
+          className="w-full px-2 py-1 text-xs rounded-md bg-white border border-gray-200 text-gray-700 hover:bg-gray-100"
+        >
+          Import from ChatGPT, Open WebUI or JSON export…
+        </button>
+        <div className="flex items-center space-x-2 text-xs">
+          <button
+            onClick={handleBackup}
+            className="flex-1 px-2 py-1 rounded-md bg-white border border-gray-200 text-gray-700 hover:bg-gray-100"
+          >
+            Back up
+          </button>
+          <button
+            onClick={handleRestore}
+            className="flex-1 px-2 py-1 rounded-md bg-white border border-gray-200 text-gray-700 hover:bg-gray-100"
+          >
+            Restore…
+          </button>
+        </div>
+        <button
+          onClick={handleEncryption}
+          className="w-full px-2 py-1 text-xs rounded-md bg-white border border-gray-200 text-gray-700 hover:bg-gray-100"
+        >
+          {encrypted ? 'Change passphrase…' : 'Encrypt chats…'}
+        </button>
+        <input
+          ref={importInputRef}
+          type="file"
+          accept=".json,application/json"
+          onChange={handleImport}
+          className="hidden"
+        />
         <div className="text-xs text-gray-500 text-center">
           {conversations.length} conversation{conversations.length !== 1 ? 's' : ''}
         </div>
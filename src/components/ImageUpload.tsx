@@ -46,10 +46,9 @@ const ImageUpload: React.FC<ImageUploadProps> = ({
   };
 
   const handleFileInputChange = (e: React.ChangeEvent<HTMLInputElement>) => {
-    const file = e.target.files?.[0];
-    if (file) {
-      handleFileSelect(file);
-    }
+    Array.from(e.target.files ?? []).forEach(handleFileSelect);
+    // Allow picking the same file again
+    e.target.value = '';
   };
 
   const handleDragOver = (e: React.DragEvent) => {
@@ -70,10 +69,7 @@ const ImageUpload: React.FC<ImageUploadProps> = ({
     
     if (disabled) return;
 
-    const file = e.dataTransfer.files[0];
-    if (file) {
-      handleFileSelect(file);
-    }
+    Array.from(e.dataTransfer.files).forEach(handleFileSelect);
   };
 
   const handlePaste = (e: ClipboardEvent) => {
@@ -94,9 +90,11 @@ const ImageUpload: React.FC<ImageUploadProps> = ({
   };
 
   React.useEffect(() => {
+    // Previews are ImageUploads too; only the attach button takes pastes
+    if (selectedImage) return;
     document.addEventListener('paste', handlePaste);
     return () => document.removeEventListener('paste', handlePaste);
-  }, [disabled]);
+  }, [disabled, selectedImage]);
 
   const handleAttachClick = () => {
     if (!disabled) {
@@ -139,6 +137,7 @@ const ImageUpload: React.FC<ImageUploadProps> = ({
         ref={fileInputRef}
         type="file"
         accept="image/*"
+        multiple
         onChange={handleFileInputChange}
         className="hidden"
         disabled={disabled}
@@ -152,7 +151,7 @@ const ImageUpload: React.FC<ImageUploadProps> = ({
             ? 'text-gray-300 cursor-not-allowed' 
             : 'text-gray-500 hover:text-gray-700 hover:bg-gray-100'
         }`}
-        title="Attach image (or drag & drop / paste)"
+        title="Attach images (or drag & drop / paste)"
       >
         <svg
           width="20"
@@ -170,7 +169,7 @@ const ImageUpload: React.FC<ImageUploadProps> = ({
 
       {isDragOver && (
         <div className="absolute inset-0 bg-blue-100 border-2 border-dashed border-blue-300 rounded-lg flex items-center justify-center">
-          <p className="text-blue-600 text-sm font-medium">Drop image here</p>
+          <p className="text-blue-600 text-sm font-medium">Drop images here</p>
         </div>
       )}
     </div>
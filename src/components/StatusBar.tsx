@@ -1,9 +1,12 @@
 import React, { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import { EmbeddingStatus } from '../types';
 
 const StatusBar: React.FC = () => {
   const [isConnected, setIsConnected] = useState<boolean | null>(null);
   const [isChecking, setIsChecking] = useState(false);
+  const [embeddingStatus, setEmbeddingStatus] = useState<EmbeddingStatus>({ state: 'ready' });
 
   const checkConnection = async () => {
     setIsChecking(true);
@@ -24,6 +27,31 @@ const StatusBar: React.FC = () => {
     return () => clearInterval(interval);
   }, []);
 
+  // The embedding worker reports failures of semantic search indexing
+  useEffect(() => {
+    invoke<EmbeddingStatus>('get_embedding_status')
+      .then(setEmbeddingStatus)
+      .catch((error) => console.error('Error getting embedding status:', error));
+    const unlisten = listen<EmbeddingStatus>('embedding-status', (event) => {
+      setEmbeddingStatus(event.payload);
+    });
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, []);
+
+  const getEmbeddingText = () => {
+    switch (embeddingStatus.state) {
+      case 'ready':
+        return null;
+      case 'retrying':
+        return `Indexing for search failed, retrying in ${embeddingStatus.retry_in_secs}s: ${embeddingStatus.error}`;
+      case 'unknown_model':
+        return `Indexing for search paused: the server has no embedding model "${embeddingStatus.model}".`;
+    }
+  };
+  const embeddingText = getEmbeddingText();
+
   const getStatusColor = () => {
     if (isChecking) return 'bg-yellow-500';
     if (isConnected === null) return 'bg-gray-500';
@@ -42,6 +70,11 @@ const StatusBar: React.FC = () => {
       <div className="flex items-center space-x-3">
         <div className={`w-2.5 h-2.5 rounded-full ${getStatusColor()} shadow-sm`}></div>
         <span className="text-sm text-gray-600 font-medium">{getStatusText()}</span>
+        {embeddingText && (
+          <span className="text-xs text-amber-600 truncate max-w-md" title={embeddingText}>
+            {embeddingText}
+          </span>
+        )}
       </div>
       <div className="flex items-center space-x-3">
         <button
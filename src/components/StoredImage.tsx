@@ -0,0 +1,48 @@
+import React, { useEffect, useState } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+
+interface StoredImageProps {
+  path: string;
+  alt: string;
+  className?: string;
+  style?: React.CSSProperties;
+}
+
+const MIME_TYPES: Record<string, string> = {
+  png: 'image/png',
+  gif: 'image/gif',
+  webp: 'image/webp',
+  bmp: 'image/bmp',
+  svg: 'image/svg+xml',
+};
+
+// Images may be encrypted on disk, so they are read through the backend
+const StoredImage: React.FC<StoredImageProps> = ({ path, alt, className, style }) => {
+  const [src, setSrc] = useState<string | null>(null);
+
+  useEffect(() => {
+    let cancelled = false;
+    invoke<string>('get_image_base64', { imagePath: path })
+      .then((data) => {
+        const extension = path.split('.').pop()?.toLowerCase() ?? '';
+        if (!cancelled) setSrc(`data:${MIME_TYPES[extension] ?? 'image/jpeg'};base64,${data}`);
+      })
+      .catch((error) => console.error('Error loading image:', error));
+    return () => {
+      cancelled = true;
+    };
+  }, [path]);
+
+  if (!src) return null;
+  return (
+    <img
+      src={src}
+      alt={alt}
+      className={className}
+      style={style}
+      onClick={() => window.open(src, '_blank')}
+    />
+  );
+};
+
+export default StoredImage;
@@ -0,0 +1,61 @@
+import React, { useState } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+
+interface UnlockScreenProps {
+  onUnlocked: () => void;
+}
+
+const UnlockScreen: React.FC<UnlockScreenProps> = ({ onUnlocked }) => {
+  const [passphrase, setPassphrase] = useState('');
+  const [error, setError] = useState<string | null>(null);
+  const [isUnlocking, setIsUnlocking] = useState(false);
+
+  const handleSubmit = async (e: React.FormEvent) => {
+    e.preventDefault();
+    if (!passphrase || isUnlocking) return;
+
+    setIsUnlocking(true);
+    setError(null);
+    try {
+      await invoke('unlock_database', { passphrase });
+      onUnlocked();
+    } catch (error) {
+      setError(String(error));
+      setPassphrase('');
+    } finally {
+      setIsUnlocking(false);
+    }
+  };
+
+  return (
+    <div className="h-screen flex items-center justify-center bg-gray-50">
+      <form
+        onSubmit={handleSubmit}
+        className="w-80 p-6 bg-white border border-gray-200 rounded-2xl shadow-sm space-y-4"
+      >
+        <div>
+          <h1 className="text-lg font-semibold text-gray-900">Unlock chats</h1>
+          <p className="text-sm text-gray-500">Your conversations are encrypted. Enter your passphrase to continue.</p>
+        </div>
+        <input
+          type="password"
+          value={passphrase}
+          onChange={(e) => setPassphrase(e.target.value)}
+          autoFocus
+          placeholder="Passphrase"
+          className="w-full px-3 py-2 border border-gray-300 rounded-lg text-sm focus:outline-none focus:ring-2 focus:ring-blue-500 focus:border-transparent"
+        />
+        {error && <p className="text-sm text-red-600">{error}</p>}
+        <button
+          type="submit"
+          disabled={!passphrase || isUnlocking}
+          className="w-full px-3 py-2 text-sm text-white bg-blue-500 hover:bg-blue-600 disabled:opacity-50 rounded-lg transition-colors"
+        >
+          {isUnlocking ? 'Unlocking…' : 'Unlock'}
+        </button>
+      </form>
+    </div>
+  );
+};
+
+export default UnlockScreen;
@@ -1,24 +1,159 @@
 export interface Message {
   id: number;
   conversation_id: number;
-  role: 'user' | 'assistant';
+  role: 'system' | 'user' | 'assistant' | 'tool';
   content: string;
+  // Reasoning a thinking model gave before its answer
+  reasoning?: string;
   input_type: 'text' | 'image' | 'mixed';
-  image_path?: string;
-  image_filename?: string;
-  image_size?: number;
+  attachments: Attachment[];
   timestamp: string;
+  status: 'complete' | 'cancelled';
+  tool_calls?: ToolCall[];
+  tool_name?: string;
+  structured_content?: unknown;
+  model?: string;
+  metrics?: ReplyMetrics;
+  parent_id?: number;
+  sibling_index: number;
+  sibling_count: number;
+}
+
+export interface Attachment {
+  path: string;
+  filename?: string;
+  size?: number;
+}
+
+/** Durations are in nanoseconds. */
+export interface ReplyMetrics {
+  total_duration?: number;
+  load_duration?: number;
+  prompt_eval_count?: number;
+  eval_count?: number;
+  eval_duration?: number;
+  model_digest?: string;
+}
+
+export interface ModelUsage {
+  model: string;
+  replies: number;
+  measured_replies: number;
+  prompt_tokens: number;
+  completion_tokens: number;
+  total_duration: number;
+  tokens_per_second?: number;
 }
 
 export interface Conversation {
   id: number;
   created_at: string;
+  title?: string;
+  updated_at: string;
+  model?: string;
+  active_leaf_id?: number;
+  endpoint_id?: number;
+  persona_id?: number;
+  provider?: ProviderKind;
+  context_policy?: ContextPolicy;
+}
+
+export type ContextPolicy = 'sliding_window' | 'system_and_recent' | 'summary';
+
+// How the history was cut down to fit the model's context
+export interface ContextWindow {
+  policy: ContextPolicy;
+  context_tokens: number;
+  estimated_tokens: number;
+  dropped_message_ids: number[];
+  dropped_count: number;
+  summarized: boolean;
+  // Why summarizing failed; the dropped messages were left out without one
+  summary_error: string | null;
+}
+
+export interface Persona {
+  id: number;
+  name: string;
+  system_prompt: string;
+  default_model?: string;
+  default_options?: GenerationOptions;
+  created_at: string;
+}
+
+export type ProviderKind = 'ollama' | 'openai';
+
+export interface Endpoint {
+  id: number;
+  name: string;
+  base_url: string;
+  provider: ProviderKind;
+  api_key?: string;
+  headers: Record<string, string>;
+  created_at: string;
+  is_selected: boolean;
 }
 
 export interface ChatMessage {
   role: string;
   content: string;
   images?: string[];
+  tool_calls?: ToolCall[];
+  tool_name?: string;
+}
+
+export interface ToolCall {
+  function: {
+    name: string;
+    arguments: Record<string, unknown>;
+  };
+}
+
+export interface ToolDefinition {
+  type: 'function';
+  function: {
+    name: string;
+    description: string;
+    parameters: Record<string, unknown>;
+  };
+}
+
+export type ChatStreamEvent =
+  | { event: 'context'; data: { window: ContextWindow } }
+  | { event: 'delta'; data: { content: string } }
+  | { event: 'thinking'; data: { content: string } }
+  | {
+      event: 'done';
+      data: {
+        model: string;
+        createdAt: string;
+        doneReason?: string;
+        content: string;
+        thinking?: string;
+        metrics: ReplyMetrics;
+      };
+    };
+
+export interface GenerationOptions {
+  temperature?: number;
+  top_p?: number;
+  top_k?: number;
+  num_ctx?: number;
+  num_predict?: number;
+  repeat_penalty?: number;
+  seed?: number;
+  stop?: string[];
+  // Sent as Ollama's top-level `think` flag rather than a model option
+  think?: boolean;
+}
+
+export interface ModelDetails {
+  parent_model?: string;
+  format?: string;
+  family?: string;
+  families?: string[];
+  parameter_size?: string;
+  quantization_level?: string;
 }
 
 export interface OllamaModel {
@@ -26,10 +161,112 @@ export interface OllamaModel {
   size: number;
   digest: string;
   modified_at: string;
+  details: ModelDetails;
+}
+
+export interface ModelInfo {
+  modelfile: string;
+  parameters: string;
+  template: string;
+  system?: string;
+  license?: string;
+  details: ModelDetails;
+  capabilities: string[];
+  model_info?: Record<string, unknown>;
+  modified_at?: string;
+}
+
+export interface RunningModel {
+  name: string;
+  model: string;
+  size: number;
+  size_vram: number;
+  digest: string;
+  details: ModelDetails;
+  expires_at: string;
+}
+
+export interface PullProgress {
+  status: string;
+  digest?: string;
+  total?: number;
+  completed?: number;
+}
+
+export interface SearchFilters {
+  role?: string;
+  from?: string;
+  to?: string;
+  model?: string;
+  has_image?: boolean;
+}
+
+export interface SearchHit {
+  message: Message;
+  conversation: Conversation;
+  // HTML-escaped excerpt with matches wrapped in <mark> tags
+  snippet: string;
+  rank: number;
+}
+
+export interface SemanticMatch {
+  score: number;
+  message: Message;
+  conversation: Conversation;
 }
 
 export interface ImageFile {
   file: File;
   preview: string;
   base64: string;
-}
\ No newline at end of file
+}
+export interface TitleUpdated {
+  conversation_id: number;
+  title: string;
+}
+
+export type EmbeddingStatus =
+  | { state: 'ready' }
+  | { state: 'retrying'; error: string; retry_in_secs: number }
+  | { state: 'unknown_model'; model: string; error: string };
+
+export type ExportFormat = 'markdown' | 'json' | 'html';
+
+export interface ImportReport {
+  source: string;
+  conversations_imported: number;
+  messages_imported: number;
+  images_imported: number;
+  duplicates_skipped: number;
+  empty_skipped: number;
+  failures: string[];
+}
+
+export interface RestoreReport {
+  backup_created_at: string;
+  conversations: number;
+  images: number;
+  previous_database: string;
+  previous_images?: string;
+  encrypted: boolean;
+}
+
+export interface EncryptionStatus {
+  encrypted: boolean;
+  locked: boolean;
+}
+
+export interface EncryptionReport {
+  images_encrypted: number;
+  plain_copies: string[];
+}
+
+export interface RemovalFailure {
+  path: string;
+  error: string;
+}
+
+export interface RemovalReport {
+  removed: string[];
+  failed: RemovalFailure[];
+}